@@ -3,8 +3,16 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use fungible_ics20_ics20_conversion::msg::{CountResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
-use fungible_ics20_ics20_conversion::state::State;
+use fungible_ics20_ics20_conversion::msg::{
+    AuditResponse, AverageRateResponse, ComplianceQueryMsg, ConversionHookMsg, ConvertOptions,
+    ConvertResultResponse, CountResponse, DepositResponse, EmissionResponse, ExecuteMsg,
+    ExportStateResponse, ExportedConfig, HooksResponse, InstantiateMsg, IsAllowedResponse,
+    MaxConvertibleResponse, ModeResponse, OffersResponse, PendingOwnershipResponse, PointsResponse,
+    QueryMsg, QuoteResponse, ReceiptMintMsg, ReceiveConversionMsg, RecentActivityResponse,
+    ReserveStatusResponse, RewardsResponse, RolesResponse, SimulateBatchResponse, SrcPathsResponse,
+    SubsidyResponse, VaultDepositMsg, VaultsResponse,
+};
+use fungible_ics20_ics20_conversion::state::{Metadata, State};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -17,4 +25,40 @@ fn main() {
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(State), &out_dir);
     export_schema(&schema_for!(CountResponse), &out_dir);
+
+    // conversions
+    export_schema(&schema_for!(ConvertOptions), &out_dir);
+    export_schema(&schema_for!(ConvertResultResponse), &out_dir);
+
+    // query responses
+    export_schema(&schema_for!(HooksResponse), &out_dir);
+    export_schema(&schema_for!(RecentActivityResponse), &out_dir);
+    export_schema(&schema_for!(RolesResponse), &out_dir);
+    export_schema(&schema_for!(PendingOwnershipResponse), &out_dir);
+    export_schema(&schema_for!(VaultsResponse), &out_dir);
+    export_schema(&schema_for!(PointsResponse), &out_dir);
+    export_schema(&schema_for!(SubsidyResponse), &out_dir);
+    export_schema(&schema_for!(EmissionResponse), &out_dir);
+    export_schema(&schema_for!(RewardsResponse), &out_dir);
+    export_schema(&schema_for!(ModeResponse), &out_dir);
+    export_schema(&schema_for!(ExportedConfig), &out_dir);
+    export_schema(&schema_for!(ExportStateResponse), &out_dir);
+    export_schema(&schema_for!(MaxConvertibleResponse), &out_dir);
+    export_schema(&schema_for!(QuoteResponse), &out_dir);
+    export_schema(&schema_for!(SrcPathsResponse), &out_dir);
+    export_schema(&schema_for!(OffersResponse), &out_dir);
+    export_schema(&schema_for!(ReserveStatusResponse), &out_dir);
+    export_schema(&schema_for!(AuditResponse), &out_dir);
+    export_schema(&schema_for!(AverageRateResponse), &out_dir);
+    export_schema(&schema_for!(SimulateBatchResponse), &out_dir);
+    export_schema(&schema_for!(Metadata), &out_dir);
+    export_schema(&schema_for!(DepositResponse), &out_dir);
+
+    // messages exchanged with other contracts
+    export_schema(&schema_for!(ConversionHookMsg), &out_dir);
+    export_schema(&schema_for!(ComplianceQueryMsg), &out_dir);
+    export_schema(&schema_for!(IsAllowedResponse), &out_dir);
+    export_schema(&schema_for!(ReceiptMintMsg), &out_dir);
+    export_schema(&schema_for!(VaultDepositMsg), &out_dir);
+    export_schema(&schema_for!(ReceiveConversionMsg), &out_dir);
 }
@@ -1,20 +1,73 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Addr, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
-    Uint128, Uint256,
+    to_binary, Addr, BankMsg, Binary, Coin, ContractInfoResponse, CosmosMsg, Decimal, Deps,
+    DepsMut, Env, Event, MemoryStorage, MessageInfo, Order, Reply, Response, StdError, StdResult,
+    Storage, SubMsg, Timestamp, Uint128, WasmQuery,
 };
 use cw2::set_contract_version;
-use cw20::{Denom, Expiration};
+use cw_storage_plus::Bound;
 
 use crate::error::ContractError;
-use crate::msg::{ConvertTokenResponse, CountResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{State, STATE};
+use crate::msg::{
+    AuditEntry, AuditResponse, AverageRateResponse, Callback, ComplianceQueryMsg,
+    ConversionHookMsg, ConvertOptions, ConvertResultResponse, CountResponse, DepositResponse,
+    Direction, EmissionResponse, ExecuteMsg, ExportStateResponse, ExportedConfig, FundsPolicy,
+    HooksResponse, ImportedRecord, InstantiateMsg, IsAllowedResponse, MaxConvertibleResponse,
+    ModeResponse, OfferInfo, OffersResponse, PendingOwnershipResponse, PointsResponse, QueryMsg,
+    QuoteResponse, ReceiptMetadata, ReceiptMintMsg, ReceiveConversionMsg, RecentActivityResponse,
+    ReserveStatusResponse, RewardsResponse, RoleGrant, RolesResponse, SimulateBatchResponse,
+    SimulatedConversion, Split, SrcPath, SrcPathsResponse, StateEntry, StateSection,
+    SubsidyResponse, ThresholdStatus, VaultDeposit, VaultDepositMsg, VaultsResponse,
+};
+use crate::pagination::clamp_limit;
+use crate::state::{
+    ActivityEntry, ActivityKind, Counters, Emission, Metadata, Mode, Offer, PairConfig,
+    PendingOwnership, RateCheckpoint, ReceiptConfig, Role, RoundTrip, State, Subsidy, ACTIVITY,
+    ACTIVITY_CAPACITY, COMPLIANCE_REGISTRY, COUNTERS, DEPOSITS, DEPOSIT_GENERATION, EMISSION,
+    HOOKS, LEDGER, METADATA, MODE, OFFERS, OFFER_COUNT, OFFER_USAGE, PAIR_CONFIG, PATH_VOLUME,
+    PENDING_OWNERSHIP, POINTS, RATE_CHECKPOINTS, RECEIPTS, RESERVE_THRESHOLDS, REWARDS,
+    REWARD_POOL, ROLES, ROUND_TRIPS, SRC_PATHS, STATE, SUBSIDY, TOTAL_DEPOSITS, VAULTS,
+};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:fungible-ics20-ics20-conversion";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// seconds an address has to wait after converting before it can convert back
+const ROUND_TRIP_COOLDOWN: u64 = 86_400;
+
+// reply id of the fire-and-forget hook notifications
+pub(crate) const HOOK_REPLY_ID: u64 = 1;
+// gas each hook notification may burn, so a misbehaving hook cannot run the conversion out of gas
+const HOOK_GAS_LIMIT: u64 = 200_000;
+// most hooks that are notified on every conversion
+const MAX_HOOKS: usize = 5;
+
+// output split weights are expressed in basis points and must add up to this
+const TOTAL_BPS: u32 = 10_000;
+
+// most decimals whose whole token still fits in a u128
+const MAX_DECIMALS: u8 = 38;
+// most records accepted by a single ImportRecords call
+const MAX_IMPORT_BATCH: usize = 50;
+// most vaults that can be whitelisted
+const MAX_VAULTS: usize = 10;
+// most source paths accepted besides the primary source denom
+const MAX_SRC_PATHS: usize = 10;
+// most addresses that can hold roles at the same time
+const MAX_ROLE_HOLDERS: usize = 10;
+// most reserve thresholds that can be configured
+const MAX_RESERVE_THRESHOLDS: usize = 5;
+// most conversions a single SimulateBatch query prices
+const MAX_SIMULATED_INPUTS: usize = 30;
+// most offers that can be live at the same time
+const MAX_OFFERS: usize = 10;
+// longest metadata field accepted, in characters
+const MAX_METADATA_LENGTH: usize = 512;
+// longest memo accepted on a conversion, in characters
+const MAX_MEMO_LENGTH: usize = 256;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -25,13 +78,20 @@ pub fn instantiate(
     let state = State {
         count: msg.count,
         owner: info.sender.clone(),
-        dest_ic20_decimals: msg.dest_ic20_decimals.clone(),
-        dest_ic20_denom: msg.dest_ic20_denom.clone(),
-        src_ic20_decimals: msg.src_ic20_decimals.clone(),
-        src_ic20_denom: msg.src_ic20_denom.clone(),
+        ownership_transfer_delay: msg.ownership_transfer_delay,
     };
+    let pair = get_pair_config(
+        msg.src_ic20_denom.clone(),
+        msg.src_ic20_decimals,
+        msg.dest_ic20_denom.clone(),
+        msg.dest_ic20_decimals,
+    )?;
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     STATE.save(deps.storage, &state)?;
+    PAIR_CONFIG.save(deps.storage, &pair)?;
+    if msg.start_paused {
+        MODE.save(deps.storage, &Mode::Paused)?;
+    }
 
     Ok(Response::new()
         .add_attribute("method", "instantiate")
@@ -39,246 +99,6161 @@ pub fn instantiate(
         .add_attribute("count", msg.count.to_string()))
 }
 
+/// Builds the pair configuration, precomputing the whole token of both sides.
+pub(crate) fn get_pair_config(
+    src_ic20_denom: String,
+    src_ic20_decimals: u8,
+    dest_ic20_denom: String,
+    dest_ic20_decimals: u8,
+) -> Result<PairConfig, ContractError> {
+    if src_ic20_decimals > MAX_DECIMALS || dest_ic20_decimals > MAX_DECIMALS {
+        return Err(ContractError::InvalidDecimals { max: MAX_DECIMALS });
+    }
+    if src_ic20_denom == dest_ic20_denom {
+        return Err(ContractError::SameDenom {});
+    }
+    Ok(PairConfig {
+        dest_ic20_decimals,
+        dest_ic20_denom,
+        src_ic20_decimals,
+        src_ic20_denom,
+        dest_whole_token: get_whole_token_representation(dest_ic20_decimals).into(),
+        src_whole_token: get_whole_token_representation(src_ic20_decimals).into(),
+    })
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    if MODE.may_load(deps.storage)? == Some(Mode::Closed) {
+        return Err(ContractError::Closed {});
+    }
     match msg {
         ExecuteMsg::Increment {} => try_increment(deps),
         ExecuteMsg::Reset { count } => try_reset(deps, info, count),
+        ExecuteMsg::Convert {
+            src_token_amount,
+            options,
+        } => convert_tokens(deps, &info, env, src_token_amount, *options),
+        ExecuteMsg::ConvertBack {} => convert_back(deps, &info, env),
+        ExecuteMsg::DepositLiquidity {} => deposit_dest_tokens(deps, &info, env),
+        ExecuteMsg::WithdrawLiquidity { amount } => try_withdraw_liquidity(deps, info, env, amount),
+        ExecuteMsg::AddSrcPath { denom } => try_add_src_path(deps, info, denom),
+        ExecuteMsg::RemoveSrcPath { denom } => try_remove_src_path(deps, info, denom),
+        ExecuteMsg::AddHook { addr } => try_add_hook(deps, info, addr),
+        ExecuteMsg::RemoveHook { addr } => try_remove_hook(deps, info, addr),
+        ExecuteMsg::AddVault { addr } => try_add_vault(deps, info, addr),
+        ExecuteMsg::RemoveVault { addr } => try_remove_vault(deps, info, addr),
+        ExecuteMsg::SetMetadata { metadata } => try_set_metadata(deps, info, metadata),
+        ExecuteMsg::FundSubsidy {} => try_fund_subsidy(deps, info),
+        ExecuteMsg::SetSubsidyBonus { bonus_bps } => try_set_subsidy_bonus(deps, info, bonus_bps),
+        ExecuteMsg::PostOffer {
+            rate_bonus_bps,
+            per_user_cap,
+            total_cap,
+            expiration,
+        } => try_post_offer(
+            deps,
+            env,
+            info,
+            Offer {
+                rate_bonus_bps,
+                per_user_cap,
+                total_cap,
+                expiration,
+                used: Uint128::zero(),
+            },
+        ),
+        ExecuteMsg::WithdrawOffer { id } => try_withdraw_offer(deps, info, id),
+        ExecuteMsg::SetEmission { emission } => try_set_emission(deps, info, emission),
+        ExecuteMsg::FundRewards {} => try_fund_rewards(deps, info),
+        ExecuteMsg::ClaimRewards {} => try_claim_rewards(deps, info),
+        ExecuteMsg::GrantRole { addr, role } => try_grant_role(deps, info, addr, role),
+        ExecuteMsg::RevokeRole { addr, role } => try_revoke_role(deps, info, addr, role),
+        ExecuteMsg::ProposeOwner { new_owner } => try_propose_owner(deps, env, info, new_owner),
+        ExecuteMsg::ProposeDaoOwner { dao } => try_propose_dao_owner(deps, env, info, dao),
+        ExecuteMsg::AcceptOwnership {} => try_accept_ownership(deps, env, info),
+        ExecuteMsg::CancelOwnershipTransfer {} => try_cancel_ownership_transfer(deps, info),
+        ExecuteMsg::SetComplianceRegistry { registry } => {
+            try_set_compliance_registry(deps, info, registry)
+        }
+        ExecuteMsg::Pause {} => try_pause(deps, info),
+        ExecuteMsg::SetReceipts {
+            nft_contract,
+            threshold,
+        } => try_set_receipts(deps, info, nft_contract, threshold),
+        ExecuteMsg::SetReserveThresholds { thresholds } => {
+            try_set_reserve_thresholds(deps, info, thresholds)
+        }
+        ExecuteMsg::UpdateConfig {
+            src_denom,
+            dest_denom,
+            src_decimals,
+            dest_decimals,
+        } => try_update_config(
+            deps,
+            info,
+            src_denom,
+            dest_denom,
+            src_decimals,
+            dest_decimals,
+        ),
+        ExecuteMsg::SetMode { mode } => try_set_mode(deps, info, mode),
+        ExecuteMsg::ImportConfig { config } => try_import_config(deps, info, *config),
+        ExecuteMsg::ImportRecords { records } => try_import_records(deps, info, records),
+        ExecuteMsg::EmergencyWithdraw { denom, recipient } => {
+            try_emergency_withdraw(deps, env, info, denom, recipient)
+        }
+        ExecuteMsg::Close { recipient } => try_close(deps, env, info, recipient),
+    }
+}
+
+/// Hook notifications are dispatched with `reply_on_error`, so a failing subscriber ends up
+/// here instead of reverting the conversion. The error is only recorded as an attribute.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        HOOK_REPLY_ID => {
+            let err = msg.result.into_result().err().unwrap_or_default();
+            Ok(Response::new()
+                .add_attribute("method", "hook_failed")
+                .add_attribute("error", err))
+        }
+        id => Err(ContractError::UnknownReplyId { id }),
     }
 }
 
 pub fn deposit_dest_tokens(
     deps: DepsMut,
     info: &MessageInfo,
-    _env: Env,
+    env: Env,
 ) -> Result<Response, ContractError> {
-    let state = STATE.load(deps.storage)?;
-    if !info.funds.iter().all(|f| f.denom == state.dest_ic20_denom) {
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let (amount, extra_coins) = split_funds(&info.funds, &pair.dest_ic20_denom);
+    if !extra_coins.is_empty() {
         return Err(ContractError::InvalidFunds {});
     }
-    return Ok(Response::new());
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    // the deposited funds are already part of the contract balance at this point
+    let total = deps
+        .querier
+        .query_balance(env.contract.address, &pair.dest_ic20_denom)?
+        .amount;
+
+    let mut counters = COUNTERS.may_load(deps.storage)?.unwrap_or_default();
+    record_activity(
+        deps.storage,
+        &mut counters,
+        ActivityEntry {
+            kind: ActivityKind::Deposit,
+            address: info.sender.clone(),
+            amount_in: amount,
+            amount_out: Uint128::zero(),
+            height: env.block.height,
+            time: env.block.time,
+            memo: None,
+        },
+    )?;
+    COUNTERS.save(deps.storage, &counters)?;
+    let generation = DEPOSIT_GENERATION
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let deposited = DEPOSITS.update(
+        deps.storage,
+        (generation, &info.sender),
+        |deposited| -> StdResult<_> { Ok(deposited.unwrap_or_default().checked_add(amount)?) },
+    )?;
+    let total_deposits = TOTAL_DEPOSITS.may_load(deps.storage)?.unwrap_or_default();
+    TOTAL_DEPOSITS.save(
+        deps.storage,
+        &total_deposits.checked_add(amount).map_err(StdError::from)?,
+    )?;
+    record_inflow(deps.storage, &pair.dest_ic20_denom, amount)?;
+
+    let event = Event::new("deposit")
+        .add_attribute("depositor", info.sender.as_str())
+        .add_attribute("denom", pair.dest_ic20_denom)
+        .add_attribute("amount", amount)
+        .add_attribute("total", total)
+        .add_attribute("deposited", deposited);
+    Ok(Response::new().add_event(event))
 }
 
-pub fn convert_tokens(
+/// Converts destination tokens back into the primary source denom at the same rate. Bonuses,
+/// points, rewards and hooks only apply to conversions in the other direction.
+pub fn convert_back(
     deps: DepsMut,
     info: &MessageInfo,
-    _env: Env,
-    src_token_amount: Uint128,
+    env: Env,
 ) -> Result<Response, ContractError> {
-    let state = STATE.load(deps.storage)?;
-    let src_denom = state.src_ic20_denom.clone();
-    // make sure it's the right token and count how much has been sent.
-    if !info.funds.iter().all(|f| f.denom == state.dest_ic20_denom) {
-        return Err(ContractError::InvalidFunds {});
+    // holders can still exit while the contract winds down
+    if MODE.may_load(deps.storage)?.unwrap_or(Mode::Active) == Mode::Paused {
+        return Err(ContractError::Paused {});
     }
-    let received_src_token_amount: Uint128 = info
-        .funds
-        .iter()
-        .filter(|c| c.denom == src_denom)
-        .map(|c| c.amount)
-        .sum();
-    if received_src_token_amount != src_token_amount {
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    ensure_allowed(deps.as_ref(), &info.sender)?;
+    let (amount_in, extra_coins) = split_funds(&info.funds, &pair.dest_ic20_denom);
+    if !extra_coins.is_empty() {
         return Err(ContractError::InvalidFunds {});
     }
+    if amount_in.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    // a round trip must not pay out bonuses as source tokens, nor farm points and rewards
+    let mut round_trip = ROUND_TRIPS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    let until = round_trip.last_conversion.plus_seconds(ROUND_TRIP_COOLDOWN);
+    if env.block.time < until {
+        return Err(ContractError::RoundTripCooldown { until });
+    }
+    if amount_in > round_trip.returnable {
+        return Err(ContractError::ExceedsReturnable {
+            returnable: round_trip.returnable,
+        });
+    }
+    let amount_out = get_reverse_conversion_output(&pair, amount_in)?;
+    let escrow = deps
+        .querier
+        .query_balance(&env.contract.address, &pair.src_ic20_denom)?
+        .amount;
+    if amount_out > escrow {
+        return Err(ContractError::InsufficientFunds {});
+    }
 
-    let out_token_amount = calculate_token_conversion_output(
-        received_src_token_amount.u128(),
-        10 * *&(state.dest_ic20_decimals.clone() as u128),
-        state.src_ic20_decimals.clone(),
-        state.dest_ic20_decimals.clone(),
+    let mut counters = COUNTERS.may_load(deps.storage)?.unwrap_or_default();
+    let id = counters.conversions;
+    counters.conversions += 1;
+    record_activity(
+        deps.storage,
+        &mut counters,
+        ActivityEntry {
+            kind: ActivityKind::ConversionBack,
+            address: info.sender.clone(),
+            amount_in,
+            amount_out,
+            height: env.block.height,
+            time: env.block.time,
+            memo: None,
+        },
     )?;
-    // convert the sent amount to the destination token denomination & decimals
+    COUNTERS.save(deps.storage, &counters)?;
+    round_trip.returnable -= amount_in;
+    // nothing is left to convert back, so the cooldown no longer matters either
+    if round_trip.returnable.is_zero() {
+        ROUND_TRIPS.remove(deps.storage, &info.sender);
+    } else {
+        ROUND_TRIPS.save(deps.storage, &info.sender, &round_trip)?;
+    }
+    POINTS.update(deps.storage, &info.sender, |points| -> StdResult<_> {
+        Ok(points.unwrap_or_default().saturating_sub(amount_in))
+    })?;
+    forfeit_rewards(deps.storage, &info.sender, amount_in)?;
+    record_inflow(deps.storage, &pair.dest_ic20_denom, amount_in)?;
+    record_outflow(deps.storage, &pair.src_ic20_denom, amount_out)?;
 
-    let transfer_msg = get_bank_transfer_to_msg(
-        &info.sender,
-        &state.dest_ic20_denom.clone(),
-        Uint128::from(out_token_amount.amount.clone()),
-    );
-    Ok(Response::new().add_message(transfer_msg))
+    let data = ConvertResultResponse {
+        id,
+        amount_out,
+        refunded: Uint128::zero(),
+    };
+    Ok(Response::new()
+        .add_attribute("method", "convert_back")
+        .add_attribute("sender", info.sender.as_str())
+        .add_attribute("amount_in", amount_in)
+        .add_attribute("amount_out", amount_out)
+        .add_message(get_bank_transfer_to_msg(
+            &info.sender,
+            &pair.src_ic20_denom,
+            amount_out,
+        ))
+        .set_data(to_binary(&data)?))
 }
 
-/// Convert between tokens with different decimals.
-///
-/// # Arguments
-///
-/// * `amount` - the amount of the input token to convert
-/// * `rate` - corresponds to the output token decimals. E.g: If we want 1:1 rate and the output token has 6 decimals, then rate = 1_000_000
-/// * `input_decimals` - the number of decimals of the input token
-/// * `output_decimals` - the number of decimals of the output token
-pub fn calculate_token_conversion_output(
-    amount: u128,
-    rate: u128,
-    input_decimals: u8,
-    output_decimals: u8,
-) -> StdResult<ConvertTokenResponse> {
-    // result = amount * rate / one whole output token
-    let mut result = amount * rate;
+pub fn try_withdraw_liquidity(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let generation = DEPOSIT_GENERATION
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let deposited = DEPOSITS
+        .may_load(deps.storage, (generation, &info.sender))?
+        .unwrap_or_default();
+    if amount > deposited {
+        return Err(ContractError::WithdrawalExceedsDeposit { deposited });
+    }
+    let remaining = deposited - amount;
+    if remaining.is_zero() {
+        DEPOSITS.remove(deps.storage, (generation, &info.sender));
+    } else {
+        DEPOSITS.save(deps.storage, (generation, &info.sender), &remaining)?;
+    }
+    let total_deposits = TOTAL_DEPOSITS.may_load(deps.storage)?.unwrap_or_default();
+    TOTAL_DEPOSITS.save(
+        deps.storage,
+        &total_deposits.checked_sub(amount).map_err(StdError::from)?,
+    )?;
+
+    let mut counters = COUNTERS.may_load(deps.storage)?.unwrap_or_default();
+    record_activity(
+        deps.storage,
+        &mut counters,
+        ActivityEntry {
+            kind: ActivityKind::Withdrawal,
+            address: info.sender.clone(),
+            amount_in: Uint128::zero(),
+            amount_out: amount,
+            height: env.block.height,
+            time: env.block.time,
+            memo: None,
+        },
+    )?;
+    COUNTERS.save(deps.storage, &counters)?;
+    record_outflow(deps.storage, &pair.dest_ic20_denom, amount)?;
+
+    let reserve_events =
+        get_reserve_low_events(deps.as_ref(), &env, &pair.dest_ic20_denom, amount)?;
+    let total = deps
+        .querier
+        .query_balance(env.contract.address, &pair.dest_ic20_denom)?
+        .amount
+        .saturating_sub(amount);
+    let event = Event::new("withdrawal")
+        .add_attribute("depositor", info.sender.as_str())
+        .add_attribute("denom", pair.dest_ic20_denom.as_str())
+        .add_attribute("amount", amount)
+        .add_attribute("total", total)
+        .add_attribute("deposited", remaining);
+    Ok(Response::new()
+        .add_event(event)
+        .add_events(reserve_events)
+        .add_message(get_bank_transfer_to_msg(
+            &info.sender,
+            &pair.dest_ic20_denom,
+            amount,
+        )))
+}
 
-    // But, if tokens have different number of decimals, we need to compensate either by
-    // dividing or multiplying (depending on which token has more decimals) the difference
-    if input_decimals < output_decimals {
-        let compensation = get_whole_token_representation(output_decimals - input_decimals);
-        result = result * compensation
-    } else if output_decimals < input_decimals {
-        let compensation = get_whole_token_representation(input_decimals - output_decimals);
-        result = result / compensation
+pub fn convert_tokens(
+    deps: DepsMut,
+    info: &MessageInfo,
+    env: Env,
+    src_token_amount: Option<Uint128>,
+    options: ConvertOptions,
+) -> Result<Response, ContractError> {
+    let ConvertOptions {
+        callback,
+        memo,
+        splits,
+        vault_deposit,
+        allow_partial,
+    } = options;
+    if let Some(memo) = &memo {
+        if memo.chars().count() > MAX_MEMO_LENGTH {
+            return Err(ContractError::MemoTooLong {
+                max: MAX_MEMO_LENGTH,
+            });
+        }
+    }
+    // the output goes to the sender, unless it is split between several recipients, deposited
+    // into a vault or attached to the callback
+    let attach_to_callback = matches!(
+        &callback,
+        Some(Callback {
+            funds_policy: FundsPolicy::Attach,
+            ..
+        })
+    );
+    let payout_targets = [
+        splits.is_some(),
+        vault_deposit.is_some(),
+        attach_to_callback,
+    ];
+    if payout_targets.iter().filter(|set| **set).count() > 1 {
+        return Err(ContractError::ConflictingPayout {});
+    }
+    let callback_target = match &callback {
+        Some(callback) => Some(validate_callback_target(
+            deps.as_ref(),
+            &env,
+            &callback.contract,
+        )?),
+        None => None,
+    };
+    match MODE.may_load(deps.storage)?.unwrap_or(Mode::Active) {
+        Mode::Paused => return Err(ContractError::Paused {}),
+        Mode::Deprecated => return Err(ContractError::Deprecated {}),
+        _ => {}
+    }
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    ensure_allowed(deps.as_ref(), &info.sender)?;
+    // make sure it's the right token and count how much has been sent. Any accepted path of
+    // the source asset will do, as long as only one is used at a time.
+    let src_denom = match info.funds.first() {
+        Some(coin) => coin.denom.clone(),
+        None => pair.src_ic20_denom.clone(),
+    };
+    let src_paths = SRC_PATHS.may_load(deps.storage)?.unwrap_or_default();
+    if src_denom != pair.src_ic20_denom && !src_paths.contains(&src_denom) {
+        return Err(ContractError::InvalidFunds {});
+    }
+    let (received_src_token_amount, extra_coins) = split_funds(&info.funds, &src_denom);
+    // without an explicit amount, everything attached is converted
+    let amount_matches = src_token_amount
+        .iter()
+        .all(|amount| *amount == received_src_token_amount);
+    if !extra_coins.is_empty() || !amount_matches {
+        return Err(ContractError::InvalidFunds {});
+    }
+    if received_src_token_amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
     }
+    // with partial fills allowed, only what the reserve covers is converted and the rest goes
+    // back to the sender
+    let mut refund = Uint128::zero();
+    if allow_partial {
+        let max_amount_in = get_partial_fill(
+            deps.as_ref(),
+            &env,
+            &pair,
+            Some(&info.sender),
+            received_src_token_amount,
+        )?;
+        if received_src_token_amount > max_amount_in {
+            if max_amount_in.is_zero() {
+                return Err(ContractError::InsufficientFunds {});
+            }
+            refund = received_src_token_amount - max_amount_in;
+        }
+    }
+    let filled_src_token_amount = received_src_token_amount - refund;
 
-    let whole_token = get_whole_token_representation(output_decimals);
+    let out_token_amount = get_conversion_output(&pair, filled_src_token_amount)?;
 
-    let result = result / whole_token;
+    // every state change happens here, before any outbound message is composed
+    let mut counters = COUNTERS.may_load(deps.storage)?.unwrap_or_default();
+    let id = counters.conversions;
+    counters.conversions += 1;
 
-    Ok(ConvertTokenResponse { amount: result })
-}
+    let promo_bonus = apply_offer(deps.storage, &env, &info.sender, out_token_amount)?;
+    ROUND_TRIPS.update(deps.storage, &info.sender, |round_trip| -> StdResult<_> {
+        let round_trip = round_trip.unwrap_or_default();
+        Ok(RoundTrip {
+            last_conversion: env.block.time,
+            returnable: round_trip.returnable.checked_add(out_token_amount)?,
+        })
+    })?;
+    let dest_token_amount = apply_subsidy(deps.storage, out_token_amount)? + promo_bonus;
+    record_activity(
+        deps.storage,
+        &mut counters,
+        ActivityEntry {
+            kind: ActivityKind::Conversion,
+            address: info.sender.clone(),
+            amount_in: filled_src_token_amount,
+            amount_out: dest_token_amount,
+            height: env.block.height,
+            time: env.block.time,
+            memo: memo.clone(),
+        },
+    )?;
+    COUNTERS.save(deps.storage, &counters)?;
+    PATH_VOLUME.update(deps.storage, &src_denom, |volume| -> StdResult<_> {
+        Ok(volume
+            .unwrap_or_default()
+            .checked_add(filled_src_token_amount)?)
+    })?;
+    accrue_points(deps.storage, &info.sender, dest_token_amount)?;
+    accrue_rewards(deps.storage, &env, &info.sender, dest_token_amount)?;
+    record_inflow(deps.storage, &src_denom, filled_src_token_amount)?;
+    record_outflow(deps.storage, &pair.dest_ic20_denom, dest_token_amount)?;
 
-/// Get the amount needed to represent 1 whole token given its decimals.
-/// Ex. Given token A that has 3 decimals, 1 A == 1000
-pub fn get_whole_token_representation(decimals: u8) -> u128 {
-    let mut whole_token = 1u128;
+    let dest_coins = vec![Coin {
+        denom: pair.dest_ic20_denom.clone(),
+        amount: dest_token_amount,
+    }];
+    let mut payout_msgs = match (splits, vault_deposit) {
+        (Some(splits), _) => get_split_transfer_msgs(
+            deps.as_ref(),
+            &splits,
+            &pair.dest_ic20_denom,
+            dest_token_amount,
+        )?,
+        (None, Some(deposit)) => {
+            vec![get_vault_deposit_msg(
+                deps.as_ref(),
+                &info.sender,
+                deposit,
+                dest_coins.clone(),
+            )?]
+        }
+        (None, None) if attach_to_callback => vec![],
+        (None, None) => vec![get_bank_transfer_to_msg(
+            &info.sender,
+            &pair.dest_ic20_denom,
+            dest_token_amount,
+        )],
+    };
+    if !refund.is_zero() {
+        payout_msgs.push(get_bank_transfer_to_msg(&info.sender, &src_denom, refund));
+    }
+    if let (Some(callback), Some(contract_addr)) = (callback, callback_target) {
+        let funds = match callback.funds_policy {
+            FundsPolicy::Attach => dest_coins,
+            FundsPolicy::SendToSender => vec![],
+        };
+        let receive = ReceiveConversionMsg {
+            sender: info.sender.to_string(),
+            amount_out: dest_token_amount,
+            id,
+            msg: callback.msg,
+        };
+        payout_msgs.push(receive.into_cosmos_msg(contract_addr, funds)?);
+    }
 
-    for _ in 0..decimals {
-        whole_token *= 10;
+    if let Some(receipts) = RECEIPTS.may_load(deps.storage)? {
+        if dest_token_amount >= receipts.threshold {
+            let mint = ReceiptMintMsg {
+                token_id: id.to_string(),
+                owner: info.sender.to_string(),
+                token_uri: None,
+                extension: ReceiptMetadata {
+                    id,
+                    src_denom: src_denom.clone(),
+                    src_amount: filled_src_token_amount,
+                    dest_denom: pair.dest_ic20_denom.clone(),
+                    dest_amount: dest_token_amount,
+                    height: env.block.height,
+                    memo: memo.clone(),
+                },
+            };
+            payout_msgs.push(mint.into_cosmos_msg(receipts.nft_contract)?);
+        }
     }
 
-    whole_token
+    // let every registered hook contract know about the conversion, without letting
+    // a broken subscriber block it
+    let hooks = HOOKS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let hook_msgs = hooks
+        .into_iter()
+        .map(|hook| {
+            ConversionHookMsg {
+                id,
+                sender: info.sender.to_string(),
+                src_amount: filled_src_token_amount,
+                dest_amount: dest_token_amount,
+            }
+            .into_cosmos_msg(hook)
+            .map(|msg| SubMsg::reply_on_error(msg, HOOK_REPLY_ID).with_gas_limit(HOOK_GAS_LIMIT))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let reserve_events = get_reserve_low_events(
+        deps.as_ref(),
+        &env,
+        &pair.dest_ic20_denom,
+        dest_token_amount,
+    )?;
+
+    let data = ConvertResultResponse {
+        id,
+        amount_out: dest_token_amount,
+        refunded: refund,
+    };
+
+    let mut res = Response::new()
+        .add_attribute("method", "convert")
+        .add_attribute("sender", info.sender.as_str())
+        .add_attribute("src_denom", src_denom)
+        .add_attribute("amount_in", filled_src_token_amount)
+        .add_attribute("amount_out", dest_token_amount)
+        .add_events(reserve_events);
+    if let Some(memo) = memo {
+        res = res.add_attribute("memo", memo);
+    }
+    Ok(res
+        .add_messages(payout_msgs)
+        .add_submessages(hook_msgs)
+        .set_data(to_binary(&data)?))
 }
 
-fn get_bank_transfer_to_msg(recipient: &Addr, denom: &str, native_amount: Uint128) -> CosmosMsg {
-    let transfer_bank_msg = cosmwasm_std::BankMsg::Send {
-        to_address: recipient.into(),
-        amount: vec![Coin {
-            denom: denom.to_string(),
-            amount: native_amount,
-        }],
+// the converter's own plumbing is off limits, so a callback cannot forge hook notifications,
+// vault deposits or receipt mints
+fn validate_callback_target(deps: Deps, env: &Env, contract: &str) -> Result<Addr, ContractError> {
+    let addr = deps.api.addr_validate(contract)?;
+    let is_receipts = match RECEIPTS.may_load(deps.storage)? {
+        Some(receipts) => receipts.nft_contract == addr,
+        None => false,
     };
+    if addr == env.contract.address
+        || is_receipts
+        || HOOKS.has(deps.storage, &addr)
+        || VAULTS.has(deps.storage, &addr)
+    {
+        return Err(ContractError::InvalidCallbackTarget {
+            addr: contract.to_string(),
+        });
+    }
+    Ok(addr)
+}
 
-    let transfer_bank_cosmos_msg: CosmosMsg = transfer_bank_msg.into();
-    transfer_bank_cosmos_msg
+pub fn try_add_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::HookManager)?;
+    let hook = deps.api.addr_validate(&addr)?;
+    if HOOKS.has(deps.storage, &hook) {
+        return Err(ContractError::HookAlreadyRegistered { addr });
+    }
+    let registered = HOOKS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .count();
+    if registered >= MAX_HOOKS {
+        return Err(ContractError::TooManyHooks { max: MAX_HOOKS });
+    }
+    HOOKS.save(deps.storage, &hook, &())?;
+
+    Ok(Response::new()
+        .add_attribute("method", "add_hook")
+        .add_attribute("hook", hook))
 }
 
-pub fn try_increment(deps: DepsMut) -> Result<Response, ContractError> {
-    STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
-        state.count += 1;
-        Ok(state)
-    })?;
+pub fn try_remove_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::HookManager)?;
+    let hook = deps.api.addr_validate(&addr)?;
+    if !HOOKS.has(deps.storage, &hook) {
+        return Err(ContractError::HookNotRegistered { addr });
+    }
+    HOOKS.remove(deps.storage, &hook);
 
-    Ok(Response::new().add_attribute("method", "try_increment"))
+    Ok(Response::new()
+        .add_attribute("method", "remove_hook")
+        .add_attribute("hook", hook))
 }
-pub fn try_reset(deps: DepsMut, info: MessageInfo, count: i32) -> Result<Response, ContractError> {
-    STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
-        if info.sender != state.owner {
-            return Err(ContractError::Unauthorized {});
-        }
-        state.count = count;
-        Ok(state)
-    })?;
-    Ok(Response::new().add_attribute("method", "reset"))
+
+/// Asks the compliance registry, if one is configured, whether `address` may convert.
+fn ensure_allowed(deps: Deps, address: &Addr) -> Result<(), ContractError> {
+    let registry = match COMPLIANCE_REGISTRY.may_load(deps.storage)? {
+        Some(registry) => registry,
+        None => return Ok(()),
+    };
+    let res: IsAllowedResponse = deps.querier.query_wasm_smart(
+        registry,
+        &ComplianceQueryMsg::IsAllowed {
+            address: address.to_string(),
+        },
+    )?;
+    if !res.allowed {
+        return Err(ContractError::AddressNotAllowed {
+            address: address.to_string(),
+        });
+    }
+    Ok(())
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetCount {} => to_binary(&query_count(deps)?),
+pub fn try_pause(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Guardian)?;
+    let mut res = Response::new().add_attribute("method", "pause");
+    // a deprecated contract already takes no conversions and stays deprecated
+    if MODE.may_load(deps.storage)?.unwrap_or(Mode::Active) == Mode::Active {
+        MODE.save(deps.storage, &Mode::Paused)?;
+        let event = Event::new("mode_changed").add_attribute("mode", format!("{:?}", Mode::Paused));
+        res = res.add_event(event);
     }
+    Ok(res)
 }
 
-fn query_count(deps: Deps) -> StdResult<CountResponse> {
+pub fn try_set_mode(
+    deps: DepsMut,
+    info: MessageInfo,
+    mode: Mode,
+) -> Result<Response, ContractError> {
     let state = STATE.load(deps.storage)?;
-    Ok(CountResponse { count: state.count })
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    // closing also moves the funds out, so it has its own message
+    if mode == Mode::Closed {
+        return Err(ContractError::CloseRequired {});
+    }
+    MODE.save(deps.storage, &mode)?;
+
+    let event = Event::new("mode_changed").add_attribute("mode", format!("{:?}", mode));
+    Ok(Response::new()
+        .add_attribute("method", "set_mode")
+        .add_event(event))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_binary};
+pub fn try_import_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    config: ExportedConfig,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if COUNTERS
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .activity
+        > 0
+    {
+        return Err(ContractError::AlreadyInUse {});
+    }
+    if config.subsidy_bonus_bps as u32 > TOTAL_BPS {
+        return Err(ContractError::InvalidBps {});
+    }
+    if config.hooks.len() > MAX_HOOKS {
+        return Err(ContractError::TooManyHooks { max: MAX_HOOKS });
+    }
+    if config.vaults.len() > MAX_VAULTS {
+        return Err(ContractError::TooManyVaults { max: MAX_VAULTS });
+    }
+    if config.src_paths.len() > MAX_SRC_PATHS {
+        return Err(ContractError::TooManySrcPaths { max: MAX_SRC_PATHS });
+    }
+    if config.roles.len() > MAX_ROLE_HOLDERS {
+        return Err(ContractError::TooManyRoleHolders {
+            max: MAX_ROLE_HOLDERS,
+        });
+    }
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+    if config.reserve_thresholds.len() > MAX_RESERVE_THRESHOLDS {
+        return Err(ContractError::TooManyThresholds {
+            max: MAX_RESERVE_THRESHOLDS,
+        });
+    }
 
-        let msg = InstantiateMsg {
-            count: 17,
-            src_ic20_decimals: 18,
-            src_ic20_denom: "erc20token".to_string(),
-            dest_ic20_decimals: 6,
-            dest_ic20_denom: "cosmostoken".to_string(),
-        };
-        let info = mock_info("creator", &coins(1000, "earth"));
+    // validate the whole configuration before any of it is saved
+    let pair = get_pair_config(
+        config.src_ic20_denom,
+        config.src_ic20_decimals,
+        config.dest_ic20_denom,
+        config.dest_ic20_decimals,
+    )?;
+    let mut src_paths: Vec<String> = vec![];
+    for denom in config.src_paths {
+        if denom == pair.dest_ic20_denom {
+            return Err(ContractError::SameDenom {});
+        }
+        if denom == pair.src_ic20_denom {
+            return Err(ContractError::SrcPathAlreadyRegistered { denom });
+        }
+        if !src_paths.contains(&denom) {
+            src_paths.push(denom);
+        }
+    }
+    let mut reserve_thresholds = config.reserve_thresholds;
+    reserve_thresholds.sort();
+    reserve_thresholds.dedup();
+    if let Some(receipts) = &config.receipts {
+        deps.api.addr_validate(receipts.nft_contract.as_str())?;
+    }
+    let hooks = config
+        .hooks
+        .iter()
+        .map(|hook| deps.api.addr_validate(hook))
+        .collect::<StdResult<Vec<_>>>()?;
+    let vaults = config
+        .vaults
+        .iter()
+        .map(|vault| deps.api.addr_validate(vault))
+        .collect::<StdResult<Vec<_>>>()?;
+    let compliance_registry = config
+        .compliance_registry
+        .map(|registry| deps.api.addr_validate(&registry))
+        .transpose()?;
+    let roles = config
+        .roles
+        .into_iter()
+        .map(|grant| Ok((deps.api.addr_validate(&grant.address)?, grant.roles)))
+        .collect::<StdResult<Vec<_>>>()?;
+    if let Some(emission) = &config.emission {
+        validate_emission(&pair, &src_paths, emission)?;
+    }
+    if let Some(metadata) = &config.metadata {
+        validate_metadata(metadata)?;
+    }
 
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+    PAIR_CONFIG.save(deps.storage, &pair)?;
+    state.ownership_transfer_delay = config.ownership_transfer_delay;
+    STATE.save(deps.storage, &state)?;
+    RESERVE_THRESHOLDS.save(deps.storage, &reserve_thresholds)?;
+    SRC_PATHS.save(deps.storage, &src_paths)?;
+    match config.receipts {
+        Some(receipts) => RECEIPTS.save(deps.storage, &receipts)?,
+        None => RECEIPTS.remove(deps.storage),
+    }
+    let registered = HOOKS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for hook in registered.iter() {
+        HOOKS.remove(deps.storage, hook);
+    }
+    for hook in hooks.iter() {
+        HOOKS.save(deps.storage, hook, &())?;
+    }
+    let registered = VAULTS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for vault in registered.iter() {
+        VAULTS.remove(deps.storage, vault);
+    }
+    for vault in vaults.iter() {
+        VAULTS.save(deps.storage, vault, &())?;
+    }
+    match compliance_registry {
+        Some(registry) => COMPLIANCE_REGISTRY.save(deps.storage, &registry)?,
+        None => COMPLIANCE_REGISTRY.remove(deps.storage),
+    }
 
-        // it worked, let's query the state
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: CountResponse = from_binary(&res).unwrap();
-        assert_eq!(17, value.count);
+    let granted = ROLES
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for addr in granted.iter() {
+        ROLES.remove(deps.storage, addr);
+    }
+    for (addr, roles) in roles.iter() {
+        ROLES.save(deps.storage, addr, roles)?;
     }
 
-    #[test]
-    fn increment() {
-        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+    let mut subsidy = SUBSIDY.may_load(deps.storage)?.unwrap_or_default();
+    subsidy.bonus_bps = config.subsidy_bonus_bps;
+    SUBSIDY.save(deps.storage, &subsidy)?;
+    match config.emission {
+        Some(emission) => EMISSION.save(deps.storage, &emission)?,
+        None => EMISSION.remove(deps.storage),
+    }
+    match config.metadata {
+        Some(metadata) => METADATA.save(deps.storage, &metadata)?,
+        None => METADATA.remove(deps.storage),
+    }
 
-        let msg = InstantiateMsg {
-            count: 17,
-            src_ic20_decimals: 18,
-            src_ic20_denom: "erc20token".to_string(),
-            dest_ic20_decimals: 6,
-            dest_ic20_denom: "cosmostoken".to_string(),
-        };
-        let info = mock_info("creator", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+    Ok(Response::new().add_attribute("method", "import_config"))
+}
 
-        // beneficiary can release it
-        let info = mock_info("anyone", &coins(2, "token"));
-        let msg = ExecuteMsg::Increment {};
-        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+pub fn try_update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    src_denom: Option<String>,
+    dest_denom: Option<String>,
+    src_decimals: Option<u8>,
+    dest_decimals: Option<u8>,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if MODE.may_load(deps.storage)? != Some(Mode::Paused) {
+        return Err(ContractError::NotPaused {});
+    }
+    let old = PAIR_CONFIG.load(deps.storage)?;
+    let pair = get_pair_config(
+        src_denom.unwrap_or_else(|| old.src_ic20_denom.clone()),
+        src_decimals.unwrap_or(old.src_ic20_decimals),
+        dest_denom.unwrap_or_else(|| old.dest_ic20_denom.clone()),
+        dest_decimals.unwrap_or(old.dest_ic20_decimals),
+    )?;
+    // whatever is owed or configured in destination units would silently change meaning
+    if pair.dest_ic20_denom != old.dest_ic20_denom
+        || pair.dest_ic20_decimals != old.dest_ic20_decimals
+    {
+        ensure_dest_unused(deps.as_ref())?;
+    }
+    // an accepted path would otherwise be both the primary source and an alias of it
+    let src_paths = SRC_PATHS.may_load(deps.storage)?.unwrap_or_default();
+    if src_paths.contains(&pair.src_ic20_denom) {
+        return Err(ContractError::SrcPathAlreadyRegistered {
+            denom: pair.src_ic20_denom,
+        });
+    }
+    PAIR_CONFIG.save(deps.storage, &pair)?;
 
-        // should increase counter by 1
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: CountResponse = from_binary(&res).unwrap();
-        assert_eq!(18, value.count);
+    let event = Event::new("update_config")
+        .add_attribute("old_src_denom", old.src_ic20_denom)
+        .add_attribute("old_src_decimals", old.src_ic20_decimals.to_string())
+        .add_attribute("old_dest_denom", old.dest_ic20_denom)
+        .add_attribute("old_dest_decimals", old.dest_ic20_decimals.to_string())
+        .add_attribute("src_denom", pair.src_ic20_denom)
+        .add_attribute("src_decimals", pair.src_ic20_decimals.to_string())
+        .add_attribute("dest_denom", pair.dest_ic20_denom)
+        .add_attribute("dest_decimals", pair.dest_ic20_decimals.to_string());
+    Ok(Response::new()
+        .add_attribute("method", "update_config")
+        .add_event(event))
+}
+
+fn ensure_dest_unused(deps: Deps) -> Result<(), ContractError> {
+    let subsidy = SUBSIDY.may_load(deps.storage)?.unwrap_or_default();
+    let thresholds = RESERVE_THRESHOLDS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let deposits = TOTAL_DEPOSITS.may_load(deps.storage)?.unwrap_or_default();
+    let in_use = !deposits.is_zero()
+        || ROUND_TRIPS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .next()
+            .is_some()
+        || OFFERS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .next()
+            .is_some()
+        || !subsidy.pool.is_zero()
+        || !thresholds.is_empty();
+    if in_use {
+        return Err(ContractError::DestInUse {});
     }
+    Ok(())
+}
 
-    #[test]
-    fn reset() {
-        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+pub fn try_import_records(
+    deps: DepsMut,
+    info: MessageInfo,
+    records: Vec<ImportedRecord>,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if MODE.may_load(deps.storage)? != Some(Mode::Paused) {
+        return Err(ContractError::NotPaused {});
+    }
+    if records.len() > MAX_IMPORT_BATCH {
+        return Err(ContractError::ImportBatchTooLarge {
+            max: MAX_IMPORT_BATCH,
+        });
+    }
+    // history can only be seeded before the contract has done anything itself
+    let mut counters = COUNTERS.may_load(deps.storage)?.unwrap_or_default();
+    if counters.activity > counters.imported {
+        return Err(ContractError::AlreadyInUse {});
+    }
+    let memo_too_long = records.iter().any(|record| match &record.memo {
+        Some(memo) => memo.chars().count() > MAX_MEMO_LENGTH,
+        None => false,
+    });
+    if memo_too_long {
+        return Err(ContractError::MemoTooLong {
+            max: MAX_MEMO_LENGTH,
+        });
+    }
 
-        let msg = InstantiateMsg {
-            count: 17,
-            src_ic20_decimals: 18,
-            src_ic20_denom: "erc20token".to_string(),
-            dest_ic20_decimals: 6,
-            dest_ic20_denom: "cosmostoken".to_string(),
-        };
-        let info = mock_info("creator", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+    for record in records.iter() {
+        let address = deps.api.addr_validate(&record.address)?;
+        counters.conversions += 1;
+        accrue_points(deps.storage, &address, record.amount_out)?;
+        record_activity(
+            deps.storage,
+            &mut counters,
+            ActivityEntry {
+                kind: ActivityKind::Conversion,
+                address,
+                amount_in: record.amount_in,
+                amount_out: record.amount_out,
+                height: record.height,
+                time: record.time,
+                memo: record.memo.clone(),
+            },
+        )?;
+        counters.imported += 1;
+    }
+    COUNTERS.save(deps.storage, &counters)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "import_records")
+        .add_attribute("records", records.len().to_string()))
+}
+
+pub fn try_emergency_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if MODE.may_load(deps.storage)? != Some(Mode::Paused) {
+        return Err(ContractError::NotPaused {});
+    }
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let balance = deps
+        .querier
+        .query_balance(env.contract.address, &denom)?
+        .amount;
+    record_outflow(deps.storage, &denom, balance)?;
+
+    // the swept reserve no longer backs the depositor and subsidy books, so both are cleared,
+    // and neither does a swept reward pool
+    let rewards_cleared = REWARD_POOL
+        .may_load(deps.storage, &denom)?
+        .unwrap_or_default();
+    REWARD_POOL.remove(deps.storage, &denom);
+    let mut deposits_cleared = Uint128::zero();
+    let mut subsidy_cleared = Uint128::zero();
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    if denom == pair.dest_ic20_denom {
+        deposits_cleared = TOTAL_DEPOSITS.may_load(deps.storage)?.unwrap_or_default();
+        TOTAL_DEPOSITS.save(deps.storage, &Uint128::zero())?;
+        let generation = DEPOSIT_GENERATION
+            .may_load(deps.storage)?
+            .unwrap_or_default();
+        DEPOSIT_GENERATION.save(deps.storage, &(generation + 1))?;
+        let mut subsidy = SUBSIDY.may_load(deps.storage)?.unwrap_or_default();
+        if !subsidy.pool.is_zero() {
+            subsidy_cleared = subsidy.pool;
+            subsidy.pool = Uint128::zero();
+            SUBSIDY.save(deps.storage, &subsidy)?;
+        }
+    }
+
+    let event = Event::new("emergency_withdraw")
+        .add_attribute("recipient", recipient.as_str())
+        .add_attribute("denom", denom.as_str())
+        .add_attribute("amount", balance)
+        .add_attribute("deposits_cleared", deposits_cleared)
+        .add_attribute("subsidy_cleared", subsidy_cleared)
+        .add_attribute("rewards_cleared", rewards_cleared);
+    let mut res = Response::new()
+        .add_attribute("method", "emergency_withdraw")
+        .add_event(event);
+    if !balance.is_zero() {
+        res = res.add_message(get_bank_transfer_to_msg(&recipient, &denom, balance));
+    }
+    Ok(res)
+}
+
+pub fn try_close(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if MODE.may_load(deps.storage)? != Some(Mode::Paused) {
+        return Err(ContractError::NotPaused {});
+    }
+    // the sweep must not take what is still owed: deposits, escrow that converted tokens can
+    // be returned for, and funded rewards
+    let deposits = TOTAL_DEPOSITS.may_load(deps.storage)?.unwrap_or_default();
+    if !deposits.is_zero() {
+        return Err(ContractError::DepositsOutstanding {});
+    }
+    let round_trips = ROUND_TRIPS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .next();
+    if round_trips.is_some() {
+        return Err(ContractError::EscrowOutstanding {});
+    }
+    let reward_pools = REWARD_POOL
+        .keys(deps.storage, None, None, Order::Ascending)
+        .next();
+    if reward_pools.is_some() {
+        return Err(ContractError::RewardsOutstanding {});
+    }
+    let recipient = deps.api.addr_validate(&recipient)?;
+    MODE.save(deps.storage, &Mode::Closed)?;
+
+    let balances = deps.querier.query_all_balances(env.contract.address)?;
+    let mut event = Event::new("contract_closed").add_attribute("recipient", recipient.as_str());
+    for coin in balances.iter() {
+        event = event.add_attribute(coin.denom.as_str(), coin.amount);
+        record_outflow(deps.storage, &coin.denom, coin.amount)?;
+    }
+    let mut res = Response::new()
+        .add_attribute("method", "close")
+        .add_event(event);
+    if !balances.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: recipient.into(),
+            amount: balances,
+        });
+    }
+    Ok(res)
+}
+
+pub fn try_set_compliance_registry(
+    deps: DepsMut,
+    info: MessageInfo,
+    registry: Option<String>,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let res = Response::new().add_attribute("method", "set_compliance_registry");
+    match registry {
+        Some(registry) => {
+            let registry = deps.api.addr_validate(&registry)?;
+            COMPLIANCE_REGISTRY.save(deps.storage, &registry)?;
+            Ok(res.add_attribute("registry", registry))
+        }
+        None => {
+            COMPLIANCE_REGISTRY.remove(deps.storage);
+            Ok(res)
+        }
+    }
+}
+
+/// One `reserve_low` event for every threshold that paying out `amount_out` takes the
+/// destination reserve below.
+fn get_reserve_low_events(
+    deps: Deps,
+    env: &Env,
+    denom: &str,
+    amount_out: Uint128,
+) -> StdResult<Vec<Event>> {
+    let thresholds = RESERVE_THRESHOLDS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    if thresholds.is_empty() {
+        return Ok(vec![]);
+    }
+    let before = deps
+        .querier
+        .query_balance(&env.contract.address, denom)?
+        .amount;
+    let after = before.saturating_sub(amount_out);
+    Ok(thresholds
+        .into_iter()
+        .filter(|threshold| *threshold <= before && *threshold > after)
+        .map(|threshold| {
+            Event::new("reserve_low")
+                .add_attribute("denom", denom)
+                .add_attribute("threshold", threshold)
+                .add_attribute("reserve", after)
+                .add_attribute("shortfall", threshold - after)
+        })
+        .collect())
+}
+
+pub fn try_set_reserve_thresholds(
+    deps: DepsMut,
+    info: MessageInfo,
+    mut thresholds: Vec<Uint128>,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Operator)?;
+    thresholds.sort();
+    thresholds.dedup();
+    if thresholds.len() > MAX_RESERVE_THRESHOLDS {
+        return Err(ContractError::TooManyThresholds {
+            max: MAX_RESERVE_THRESHOLDS,
+        });
+    }
+    RESERVE_THRESHOLDS.save(deps.storage, &thresholds)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_reserve_thresholds")
+        .add_attribute("count", thresholds.len().to_string()))
+}
+
+pub fn try_set_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    metadata: Metadata,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Operator)?;
+    validate_metadata(&metadata)?;
+    METADATA.save(deps.storage, &metadata)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_metadata")
+        .add_attribute("name", metadata.name))
+}
+
+fn validate_metadata(metadata: &Metadata) -> Result<(), ContractError> {
+    let fields = [
+        Some(&metadata.name),
+        Some(&metadata.description),
+        metadata.logo_uri.as_ref(),
+        metadata.docs_uri.as_ref(),
+    ];
+    let too_long = fields
+        .iter()
+        .flatten()
+        .any(|field| field.chars().count() > MAX_METADATA_LENGTH);
+    if too_long {
+        return Err(ContractError::MetadataTooLong {
+            max: MAX_METADATA_LENGTH,
+        });
+    }
+    Ok(())
+}
+
+pub fn try_set_receipts(
+    deps: DepsMut,
+    info: MessageInfo,
+    nft_contract: Option<String>,
+    threshold: Uint128,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Operator)?;
+    let res = Response::new().add_attribute("method", "set_receipts");
+    match nft_contract {
+        Some(nft_contract) => {
+            let nft_contract = deps.api.addr_validate(&nft_contract)?;
+            let receipts = ReceiptConfig {
+                nft_contract,
+                threshold,
+            };
+            RECEIPTS.save(deps.storage, &receipts)?;
+            Ok(res
+                .add_attribute("nft_contract", receipts.nft_contract)
+                .add_attribute("threshold", threshold))
+        }
+        None => {
+            RECEIPTS.remove(deps.storage);
+            Ok(res)
+        }
+    }
+}
+
+/// Appends an entry to the recent activity ring buffer, overwriting the oldest one once full.
+/// The caller saves `counters` once it is done updating them.
+fn record_activity(
+    storage: &mut dyn Storage,
+    counters: &mut Counters,
+    entry: ActivityEntry,
+) -> StdResult<()> {
+    if entry.kind == ActivityKind::Conversion {
+        let converted = &mut counters.converted;
+        let checkpoint = RateCheckpoint {
+            time: entry.time,
+            before: converted.clone(),
+        };
+        RATE_CHECKPOINTS.save(storage, converted.count % ACTIVITY_CAPACITY, &checkpoint)?;
+        converted.count += 1;
+        converted.amount_in = converted.amount_in.checked_add(entry.amount_in)?;
+        converted.amount_out = converted.amount_out.checked_add(entry.amount_out)?;
+    }
+    ACTIVITY.save(storage, counters.activity % ACTIVITY_CAPACITY, &entry)?;
+    counters.activity += 1;
+    Ok(())
+}
+
+/// Converts a source amount to the destination token decimals, one whole token for another.
+fn get_conversion_output(pair: &PairConfig, amount_in: Uint128) -> Result<Uint128, ContractError> {
+    amount_in
+        .checked_multiply_ratio(pair.dest_whole_token, pair.src_whole_token)
+        .map_err(|_| ContractError::ConversionOverflow {})
+}
+
+/// Converts a destination amount back to the source token decimals.
+fn get_reverse_conversion_output(
+    pair: &PairConfig,
+    amount_in: Uint128,
+) -> Result<Uint128, ContractError> {
+    amount_in
+        .checked_multiply_ratio(pair.src_whole_token, pair.dest_whole_token)
+        .map_err(|_| ContractError::ConversionOverflow {})
+}
+
+/// The subsidy bonus owed on top of a conversion output. Fails rather than silently dropping
+/// the bonus once the pool cannot cover it.
+fn get_subsidy_bonus(subsidy: &Subsidy, amount: Uint128) -> Result<Uint128, ContractError> {
+    let bonus = amount.multiply_ratio(subsidy.bonus_bps, TOTAL_BPS);
+    if bonus > subsidy.pool {
+        return Err(ContractError::SubsidyExhausted {});
+    }
+    Ok(bonus)
+}
+
+/// Largest part of `amount_in` whose output, subsidy and best offer bonus included, the reserve
+/// and the subsidy pool can cover. A smaller fill can only qualify for a better offer, so this
+/// shrinks the fill until the applicable bonus stops changing. Without a sender no offer
+/// applies.
+fn get_partial_fill(
+    deps: Deps,
+    env: &Env,
+    pair: &PairConfig,
+    sender: Option<&Addr>,
+    amount_in: Uint128,
+) -> Result<Uint128, ContractError> {
+    let reserve = deps
+        .querier
+        .query_balance(&env.contract.address, &pair.dest_ic20_denom)?
+        .amount;
+    let subsidy = SUBSIDY.may_load(deps.storage)?.unwrap_or_default();
+    let mut fill = amount_in;
+    loop {
+        let out = get_conversion_output(pair, fill)?;
+        let offer = match sender {
+            Some(sender) => find_best_offer(deps.storage, env, sender, out)?,
+            None => None,
+        };
+        let offer_bps = match offer {
+            Some((_, offer)) => offer.rate_bonus_bps as u32,
+            None => 0,
+        };
+        let bonus_bps = subsidy.bonus_bps as u32 + offer_bps;
+        let mut max_out = reserve.multiply_ratio(TOTAL_BPS, TOTAL_BPS + bonus_bps);
+        if subsidy.bonus_bps > 0 {
+            max_out = max_out.min(subsidy.pool.multiply_ratio(TOTAL_BPS, subsidy.bonus_bps));
+        }
+        let max_in = get_reverse_conversion_output(pair, max_out)?;
+        if fill <= max_in {
+            return Ok(fill);
+        }
+        fill = max_in;
+    }
+}
+
+/// Adds the subsidy bonus to a conversion output, paying it out of the subsidy pool.
+fn apply_subsidy(storage: &mut dyn Storage, amount: Uint128) -> Result<Uint128, ContractError> {
+    let mut subsidy = SUBSIDY.may_load(storage)?.unwrap_or_default();
+    let bonus = get_subsidy_bonus(&subsidy, amount)?;
+    if !bonus.is_zero() {
+        subsidy.pool -= bonus;
+        SUBSIDY.save(storage, &subsidy)?;
+    }
+    Ok(amount + bonus)
+}
+
+/// Books `amount` of `denom` received by the contract on the internal ledger.
+fn record_inflow(storage: &mut dyn Storage, denom: &str, amount: Uint128) -> StdResult<()> {
+    LEDGER.update(storage, denom, |entry| -> StdResult<_> {
+        let mut entry = entry.unwrap_or_default();
+        entry.inflows = entry.inflows.checked_add(amount)?;
+        Ok(entry)
+    })?;
+    Ok(())
+}
+
+/// Books `amount` of `denom` sent out by the contract on the internal ledger.
+fn record_outflow(storage: &mut dyn Storage, denom: &str, amount: Uint128) -> StdResult<()> {
+    LEDGER.update(storage, denom, |entry| -> StdResult<_> {
+        let mut entry = entry.unwrap_or_default();
+        entry.outflows = entry.outflows.checked_add(amount)?;
+        Ok(entry)
+    })?;
+    Ok(())
+}
+
+pub fn try_fund_subsidy(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::IncentiveManager)?;
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let (amount, extra_coins) = split_funds(&info.funds, &pair.dest_ic20_denom);
+    if !extra_coins.is_empty() {
+        return Err(ContractError::InvalidFunds {});
+    }
+    let mut subsidy = SUBSIDY.may_load(deps.storage)?.unwrap_or_default();
+    subsidy.pool = subsidy.pool.checked_add(amount).map_err(StdError::from)?;
+    SUBSIDY.save(deps.storage, &subsidy)?;
+    record_inflow(deps.storage, &pair.dest_ic20_denom, amount)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "fund_subsidy")
+        .add_attribute("amount", amount)
+        .add_attribute("pool", subsidy.pool))
+}
+
+pub fn try_set_subsidy_bonus(
+    deps: DepsMut,
+    info: MessageInfo,
+    bonus_bps: u16,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::IncentiveManager)?;
+    if bonus_bps as u32 > TOTAL_BPS {
+        return Err(ContractError::InvalidBps {});
+    }
+    let mut subsidy = SUBSIDY.may_load(deps.storage)?.unwrap_or_default();
+    subsidy.bonus_bps = bonus_bps;
+    SUBSIDY.save(deps.storage, &subsidy)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_subsidy_bonus")
+        .add_attribute("bonus_bps", bonus_bps.to_string()))
+}
+
+/// The live offer paying the highest bonus that still has room for `amount` under both of
+/// its caps.
+fn find_best_offer(
+    storage: &dyn Storage,
+    env: &Env,
+    sender: &Addr,
+    amount: Uint128,
+) -> StdResult<Option<(u64, Offer)>> {
+    let mut best: Option<(u64, Offer)> = None;
+    for item in OFFERS.range(storage, None, None, Order::Ascending) {
+        let (id, offer) = item?;
+        if offer.expiration.is_expired(&env.block) || offer.used + amount > offer.total_cap {
+            continue;
+        }
+        let used = OFFER_USAGE
+            .may_load(storage, (id, sender))?
+            .unwrap_or_default();
+        if used + amount > offer.per_user_cap {
+            continue;
+        }
+        let better = match &best {
+            Some((_, best)) => offer.rate_bonus_bps > best.rate_bonus_bps,
+            None => true,
+        };
+        if better {
+            best = Some((id, offer));
+        }
+    }
+    Ok(best)
+}
+
+/// Applies the best offer to a conversion output, using up room under its caps, and returns
+/// the bonus it pays.
+fn apply_offer(
+    storage: &mut dyn Storage,
+    env: &Env,
+    sender: &Addr,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let (id, mut offer) = match find_best_offer(storage, env, sender, amount)? {
+        Some(best) => best,
+        None => return Ok(Uint128::zero()),
+    };
+    offer.used += amount;
+    OFFERS.save(storage, id, &offer)?;
+    OFFER_USAGE.update(storage, (id, sender), |used| -> StdResult<_> {
+        Ok(used.unwrap_or_default() + amount)
+    })?;
+    Ok(amount.multiply_ratio(offer.rate_bonus_bps, TOTAL_BPS))
+}
+
+pub fn try_post_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    offer: Offer,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::IncentiveManager)?;
+    if offer.rate_bonus_bps as u32 > TOTAL_BPS {
+        return Err(ContractError::InvalidBps {});
+    }
+
+    // make room by dropping offers nobody can use anymore
+    let offers = OFFERS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut live = 0;
+    for (id, offer) in offers {
+        if offer.expiration.is_expired(&env.block) || offer.used >= offer.total_cap {
+            OFFERS.remove(deps.storage, id);
+        } else {
+            live += 1;
+        }
+    }
+    if live >= MAX_OFFERS {
+        return Err(ContractError::TooManyOffers { max: MAX_OFFERS });
+    }
+
+    let id = OFFER_COUNT.may_load(deps.storage)?.unwrap_or_default();
+    OFFER_COUNT.save(deps.storage, &(id + 1))?;
+    OFFERS.save(deps.storage, id, &offer)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "post_offer")
+        .add_attribute("offer_id", id.to_string())
+        .add_attribute("rate_bonus_bps", offer.rate_bonus_bps.to_string())
+        .add_attribute("expiration", offer.expiration.to_string()))
+}
+
+pub fn try_withdraw_offer(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::IncentiveManager)?;
+    if !OFFERS.has(deps.storage, id) {
+        return Err(ContractError::OfferNotFound { id });
+    }
+    OFFERS.remove(deps.storage, id);
+
+    Ok(Response::new()
+        .add_attribute("method", "withdraw_offer")
+        .add_attribute("offer_id", id.to_string()))
+}
+
+/// Credits `address` with one loyalty point per unit of destination token it converted to.
+fn accrue_points(storage: &mut dyn Storage, address: &Addr, amount: Uint128) -> StdResult<()> {
+    POINTS.update(storage, address, |points| -> StdResult<_> {
+        Ok(points.unwrap_or_default().checked_add(amount)?)
+    })?;
+    Ok(())
+}
+
+/// Credits `address` with the emission reward for converting to `amount` destination tokens,
+/// while an emission is running.
+fn accrue_rewards(
+    storage: &mut dyn Storage,
+    env: &Env,
+    address: &Addr,
+    amount: Uint128,
+) -> StdResult<()> {
+    let emission = match EMISSION.may_load(storage)? {
+        Some(emission) if env.block.time < emission.end => emission,
+        _ => return Ok(()),
+    };
+    let reward = amount * emission.per_unit;
+    if reward.is_zero() {
+        return Ok(());
+    }
+    REWARDS.update(
+        storage,
+        (address, emission.denom.as_str()),
+        |rewards| -> StdResult<_> { Ok(rewards.unwrap_or_default().checked_add(reward)?) },
+    )?;
+    Ok(())
+}
+
+/// Takes back the rewards the current emission credited for `amount` destination tokens.
+fn forfeit_rewards(storage: &mut dyn Storage, address: &Addr, amount: Uint128) -> StdResult<()> {
+    let emission = match EMISSION.may_load(storage)? {
+        Some(emission) => emission,
+        None => return Ok(()),
+    };
+    let key = (address, emission.denom.as_str());
+    let rewards = REWARDS.may_load(storage, key)?.unwrap_or_default();
+    let remaining = rewards.saturating_sub(amount * emission.per_unit);
+    if remaining.is_zero() {
+        REWARDS.remove(storage, key);
+    } else {
+        REWARDS.save(storage, key, &remaining)?;
+    }
+    Ok(())
+}
+
+pub fn try_set_emission(
+    deps: DepsMut,
+    info: MessageInfo,
+    emission: Option<Emission>,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::IncentiveManager)?;
+    let response = Response::new().add_attribute("method", "set_emission");
+    match emission {
+        Some(emission) => {
+            let pair = PAIR_CONFIG.load(deps.storage)?;
+            let src_paths = SRC_PATHS.may_load(deps.storage)?.unwrap_or_default();
+            validate_emission(&pair, &src_paths, &emission)?;
+            EMISSION.save(deps.storage, &emission)?;
+            Ok(response
+                .add_attribute("denom", emission.denom)
+                .add_attribute("per_unit", emission.per_unit.to_string())
+                .add_attribute("end", emission.end.to_string()))
+        }
+        None => {
+            EMISSION.remove(deps.storage);
+            Ok(response)
+        }
+    }
+}
+
+// rewards are paid from their own pool, so they must never be a denom the conversion holds
+fn validate_emission(
+    pair: &PairConfig,
+    src_paths: &[String],
+    emission: &Emission,
+) -> Result<(), ContractError> {
+    if emission.denom == pair.src_ic20_denom
+        || emission.denom == pair.dest_ic20_denom
+        || src_paths.contains(&emission.denom)
+    {
+        return Err(ContractError::InvalidEmissionDenom {
+            denom: emission.denom.clone(),
+        });
+    }
+    Ok(())
+}
+
+pub fn try_fund_rewards(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::IncentiveManager)?;
+    let emission = EMISSION
+        .may_load(deps.storage)?
+        .ok_or(ContractError::InvalidFunds {})?;
+    let (amount, extra_coins) = split_funds(&info.funds, &emission.denom);
+    if !extra_coins.is_empty() {
+        return Err(ContractError::InvalidFunds {});
+    }
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    let pool = REWARD_POOL.update(deps.storage, &emission.denom, |pool| -> StdResult<_> {
+        Ok(pool.unwrap_or_default().checked_add(amount)?)
+    })?;
+    record_inflow(deps.storage, &emission.denom, amount)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "fund_rewards")
+        .add_attribute("denom", emission.denom)
+        .add_attribute("amount", amount)
+        .add_attribute("pool", pool))
+}
+
+pub fn try_claim_rewards(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let rewards = load_rewards(deps.as_ref(), &info.sender)?;
+    let mut paid = vec![];
+    for reward in rewards.into_iter() {
+        let denom = reward.denom.as_str();
+        let pool = REWARD_POOL
+            .may_load(deps.storage, denom)?
+            .unwrap_or_default();
+        let amount = reward.amount.min(pool);
+        let owed = reward.amount - amount;
+        if owed.is_zero() {
+            REWARDS.remove(deps.storage, (&info.sender, denom));
+        } else {
+            REWARDS.save(deps.storage, (&info.sender, denom), &owed)?;
+        }
+        if amount.is_zero() {
+            continue;
+        }
+        if pool == amount {
+            REWARD_POOL.remove(deps.storage, denom);
+        } else {
+            REWARD_POOL.save(deps.storage, denom, &(pool - amount))?;
+        }
+        record_outflow(deps.storage, denom, amount)?;
+        paid.push(Coin {
+            denom: reward.denom,
+            amount,
+        });
+    }
+
+    let mut response = Response::new().add_attribute("method", "claim_rewards");
+    if !paid.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: paid,
+        });
+    }
+    Ok(response)
+}
+
+fn load_rewards(deps: Deps, address: &Addr) -> StdResult<Vec<Coin>> {
+    REWARDS
+        .prefix(address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect()
+}
+
+/// Get the amount needed to represent 1 whole token given its decimals.
+/// Ex. Given token A that has 3 decimals, 1 A == 1000
+pub fn get_whole_token_representation(decimals: u8) -> u128 {
+    10u128.pow(decimals as u32)
+}
+
+/// Sums the coins of `denom` in `funds` in a single pass, handing back every other coin so
+/// the caller decides whether extras are acceptable.
+fn split_funds(funds: &[Coin], denom: &str) -> (Uint128, Vec<Coin>) {
+    let mut amount = Uint128::zero();
+    let mut extra_coins = vec![];
+    for coin in funds {
+        if coin.denom == denom {
+            amount += coin.amount;
+        } else {
+            extra_coins.push(coin.clone());
+        }
+    }
+    (amount, extra_coins)
+}
+
+/// Deposits `funds` into one of the whitelisted vaults using the caller supplied message.
+fn get_vault_deposit_msg(
+    deps: Deps,
+    sender: &Addr,
+    deposit: VaultDeposit,
+    funds: Vec<Coin>,
+) -> Result<CosmosMsg, ContractError> {
+    let vault = deps.api.addr_validate(&deposit.vault)?;
+    if !VAULTS.has(deps.storage, &vault) {
+        return Err(ContractError::VaultNotRegistered {
+            addr: deposit.vault,
+        });
+    }
+    let recipient = match deposit.recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => sender.clone(),
+    };
+    let deposit = VaultDepositMsg {
+        recipient: recipient.to_string(),
+    };
+    Ok(deposit.into_cosmos_msg(vault, funds)?)
+}
+
+/// Divides `amount` between the split recipients according to their weights. Rounding dust
+/// goes to the last recipient so that the whole amount is always paid out.
+fn get_split_transfer_msgs(
+    deps: Deps,
+    splits: &[Split],
+    denom: &str,
+    amount: Uint128,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let total_bps: u32 = splits.iter().map(|split| split.bps as u32).sum();
+    if splits.is_empty() || total_bps != TOTAL_BPS {
+        return Err(ContractError::InvalidSplits {});
+    }
+
+    let mut remaining = amount;
+    let mut msgs = vec![];
+    for (i, split) in splits.iter().enumerate() {
+        let recipient = deps.api.addr_validate(&split.recipient)?;
+        let share = if i == splits.len() - 1 {
+            remaining
+        } else {
+            amount.multiply_ratio(split.bps, TOTAL_BPS)
+        };
+        remaining -= share;
+        if !share.is_zero() {
+            msgs.push(get_bank_transfer_to_msg(&recipient, denom, share));
+        }
+    }
+    Ok(msgs)
+}
+
+fn get_bank_transfer_to_msg(recipient: &Addr, denom: &str, native_amount: Uint128) -> CosmosMsg {
+    let transfer_bank_msg = cosmwasm_std::BankMsg::Send {
+        to_address: recipient.into(),
+        amount: vec![Coin {
+            denom: denom.to_string(),
+            amount: native_amount,
+        }],
+    };
+
+    let transfer_bank_cosmos_msg: CosmosMsg = transfer_bank_msg.into();
+    transfer_bank_cosmos_msg
+}
+
+pub fn try_add_src_path(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let mut src_paths = SRC_PATHS.may_load(deps.storage)?.unwrap_or_default();
+    if denom == pair.src_ic20_denom || src_paths.contains(&denom) {
+        return Err(ContractError::SrcPathAlreadyRegistered { denom });
+    }
+    if src_paths.len() >= MAX_SRC_PATHS {
+        return Err(ContractError::TooManySrcPaths { max: MAX_SRC_PATHS });
+    }
+    src_paths.push(denom.clone());
+    SRC_PATHS.save(deps.storage, &src_paths)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "add_src_path")
+        .add_attribute("denom", denom))
+}
+
+pub fn try_remove_src_path(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut src_paths = SRC_PATHS.may_load(deps.storage)?.unwrap_or_default();
+    if !src_paths.contains(&denom) {
+        return Err(ContractError::SrcPathNotRegistered { denom });
+    }
+    src_paths.retain(|path| path != &denom);
+    SRC_PATHS.save(deps.storage, &src_paths)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "remove_src_path")
+        .add_attribute("denom", denom))
+}
+
+pub fn try_add_vault(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Operator)?;
+    let vault = deps.api.addr_validate(&addr)?;
+    if VAULTS.has(deps.storage, &vault) {
+        return Err(ContractError::VaultAlreadyRegistered { addr });
+    }
+    let registered = VAULTS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .count();
+    if registered >= MAX_VAULTS {
+        return Err(ContractError::TooManyVaults { max: MAX_VAULTS });
+    }
+    VAULTS.save(deps.storage, &vault, &())?;
+
+    Ok(Response::new()
+        .add_attribute("method", "add_vault")
+        .add_attribute("vault", vault))
+}
+
+pub fn try_remove_vault(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Operator)?;
+    let vault = deps.api.addr_validate(&addr)?;
+    if !VAULTS.has(deps.storage, &vault) {
+        return Err(ContractError::VaultNotRegistered { addr });
+    }
+    VAULTS.remove(deps.storage, &vault);
+
+    Ok(Response::new()
+        .add_attribute("method", "remove_vault")
+        .add_attribute("vault", vault))
+}
+
+/// Fails with `Unauthorized` unless `sender` is the owner or has been granted `role`.
+fn ensure_role(deps: Deps, sender: &Addr, role: Role) -> Result<(), ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if *sender == state.owner {
+        return Ok(());
+    }
+    let roles = ROLES.may_load(deps.storage, sender)?.unwrap_or_default();
+    if !roles.contains(&role) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+pub fn try_grant_role(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+    role: Role,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let addr = deps.api.addr_validate(&addr)?;
+    let mut roles = ROLES.may_load(deps.storage, &addr)?.unwrap_or_default();
+    if roles.contains(&role) {
+        return Err(ContractError::RoleAlreadyGranted {});
+    }
+    if roles.is_empty() {
+        let holders = ROLES
+            .keys(deps.storage, None, None, Order::Ascending)
+            .count();
+        if holders >= MAX_ROLE_HOLDERS {
+            return Err(ContractError::TooManyRoleHolders {
+                max: MAX_ROLE_HOLDERS,
+            });
+        }
+    }
+    roles.push(role);
+    ROLES.save(deps.storage, &addr, &roles)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "grant_role")
+        .add_attribute("addr", addr))
+}
+
+pub fn try_revoke_role(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+    role: Role,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let addr = deps.api.addr_validate(&addr)?;
+    let mut roles = ROLES.may_load(deps.storage, &addr)?.unwrap_or_default();
+    if !roles.contains(&role) {
+        return Err(ContractError::RoleNotGranted {});
+    }
+    roles.retain(|r| r != &role);
+    if roles.is_empty() {
+        ROLES.remove(deps.storage, &addr);
+    } else {
+        ROLES.save(deps.storage, &addr, &roles)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "revoke_role")
+        .add_attribute("addr", addr))
+}
+
+pub fn try_propose_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_owner: String,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let new_owner = deps.api.addr_validate(&new_owner)?;
+    propose_owner(deps, env, state, new_owner)
+}
+
+/// Like `try_propose_owner`, but makes sure the new owner is a contract. The DAO then
+/// acknowledges the handover by executing `AcceptOwnership` itself, so control can never
+/// end up with an address nobody can act for.
+pub fn try_propose_dao_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    dao: String,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let new_owner = deps.api.addr_validate(&dao)?;
+    let query = WasmQuery::ContractInfo {
+        contract_addr: new_owner.to_string(),
+    };
+    deps.querier
+        .query::<ContractInfoResponse>(&query.into())
+        .map_err(|_| ContractError::NotAContract { addr: dao })?;
+    propose_owner(deps, env, state, new_owner)
+}
+
+fn propose_owner(
+    deps: DepsMut,
+    env: Env,
+    state: State,
+    new_owner: Addr,
+) -> Result<Response, ContractError> {
+    let pending = PendingOwnership {
+        new_owner,
+        accept_after: env.block.time.plus_seconds(state.ownership_transfer_delay),
+    };
+    PENDING_OWNERSHIP.save(deps.storage, &pending)?;
+
+    let event = Event::new("ownership_transfer_proposed")
+        .add_attribute("owner", state.owner)
+        .add_attribute("new_owner", pending.new_owner)
+        .add_attribute("accept_after", pending.accept_after.to_string());
+    Ok(Response::new()
+        .add_attribute("method", "propose_owner")
+        .add_event(event))
+}
+
+pub fn try_accept_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let pending = PENDING_OWNERSHIP
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingOwnership {})?;
+    if info.sender != pending.new_owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if env.block.time < pending.accept_after {
+        return Err(ContractError::OwnershipTransferLocked {
+            accept_after: pending.accept_after,
+        });
+    }
+    let state = STATE.update(deps.storage, |mut state| -> StdResult<_> {
+        state.owner = pending.new_owner;
+        Ok(state)
+    })?;
+    PENDING_OWNERSHIP.remove(deps.storage);
+
+    let event = Event::new("ownership_transfer_accepted").add_attribute("owner", state.owner);
+    Ok(Response::new()
+        .add_attribute("method", "accept_ownership")
+        .add_event(event))
+}
+
+pub fn try_cancel_ownership_transfer(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    if info.sender != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let pending = PENDING_OWNERSHIP
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingOwnership {})?;
+    PENDING_OWNERSHIP.remove(deps.storage);
+
+    let event = Event::new("ownership_transfer_cancelled")
+        .add_attribute("owner", state.owner)
+        .add_attribute("new_owner", pending.new_owner);
+    Ok(Response::new()
+        .add_attribute("method", "cancel_ownership_transfer")
+        .add_event(event))
+}
+
+pub fn try_increment(deps: DepsMut) -> Result<Response, ContractError> {
+    STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
+        state.count += 1;
+        Ok(state)
+    })?;
+
+    Ok(Response::new().add_attribute("method", "try_increment"))
+}
+pub fn try_reset(deps: DepsMut, info: MessageInfo, count: i32) -> Result<Response, ContractError> {
+    STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.count = count;
+        Ok(state)
+    })?;
+    Ok(Response::new().add_attribute("method", "reset"))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetCount {} => to_binary(&query_count(deps)?),
+        QueryMsg::Hooks { start_after, limit } => {
+            to_binary(&query_hooks(deps, start_after, limit)?)
+        }
+        QueryMsg::RecentActivity { start_after, limit } => {
+            to_binary(&query_recent_activity(deps, start_after, limit)?)
+        }
+        QueryMsg::AverageRate { last, since } => to_binary(&query_average_rate(deps, last, since)?),
+        QueryMsg::Metadata {} => to_binary(&METADATA.may_load(deps.storage)?.unwrap_or_default()),
+        QueryMsg::Subsidy {} => to_binary(&query_subsidy(deps)?),
+        QueryMsg::Emission {} => to_binary(&query_emission(deps)?),
+        QueryMsg::Rewards {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&query_rewards(deps, address, start_after, limit)?),
+        QueryMsg::Deposit { address } => to_binary(&query_deposit(deps, address)?),
+        QueryMsg::Points { address } => to_binary(&query_points(deps, address)?),
+        QueryMsg::Vaults { start_after, limit } => {
+            to_binary(&query_vaults(deps, start_after, limit)?)
+        }
+        QueryMsg::Roles { address } => to_binary(&query_roles(deps, address)?),
+        QueryMsg::PendingOwnership {} => to_binary(&query_pending_ownership(deps)?),
+        QueryMsg::Mode {} => to_binary(&query_mode(deps)?),
+        QueryMsg::ExportConfig {} => to_binary(&query_export_config(deps)?),
+        QueryMsg::SrcPaths {} => to_binary(&query_src_paths(deps)?),
+        QueryMsg::Quote { amount_in, sender } => {
+            to_binary(&query_quote(deps, env, amount_in, sender)?)
+        }
+        QueryMsg::SimulateBatch { inputs, sender } => {
+            to_binary(&query_simulate_batch(deps, env, inputs, sender)?)
+        }
+        QueryMsg::Offers { start_after, limit } => {
+            to_binary(&query_offers(deps, env, start_after, limit)?)
+        }
+        QueryMsg::Audit { start_after, limit } => {
+            to_binary(&query_audit(deps, env, start_after, limit)?)
+        }
+        QueryMsg::ReserveStatus {} => to_binary(&query_reserve_status(deps, env)?),
+        QueryMsg::MaxConvertible { direction, address } => {
+            to_binary(&query_max_convertible(deps, env, direction, address)?)
+        }
+        QueryMsg::ExportState {
+            section,
+            start_after,
+            limit,
+        } => to_binary(&query_export_state(deps, section, start_after, limit)?),
+    }
+}
+
+fn query_count(deps: Deps) -> StdResult<CountResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(CountResponse { count: state.count })
+}
+
+fn query_recent_activity(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<RecentActivityResponse> {
+    let count = COUNTERS
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .activity;
+    // newest first, so the page ends before the cursor; older entries have been overwritten
+    let end = start_after.unwrap_or(count).min(count);
+    let seqs: Vec<u64> = (count.saturating_sub(ACTIVITY_CAPACITY)..end)
+        .rev()
+        .take(clamp_limit(limit))
+        .collect();
+    let entries = seqs
+        .iter()
+        .map(|seq| ACTIVITY.load(deps.storage, seq % ACTIVITY_CAPACITY))
+        .collect::<StdResult<_>>()?;
+    Ok(RecentActivityResponse {
+        entries,
+        last_seq: seqs.last().copied(),
+    })
+}
+
+fn query_average_rate(
+    deps: Deps,
+    last: Option<u32>,
+    since: Option<Timestamp>,
+) -> StdResult<AverageRateResponse> {
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let totals = COUNTERS
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .converted;
+    // the window runs from the `first` conversion to the latest one
+    let mut first = totals.count.saturating_sub(ACTIVITY_CAPACITY);
+    if let Some(last) = last {
+        first = first.max(totals.count.saturating_sub(last as u64));
+    }
+    if let Some(since) = since {
+        // checkpoints are in time order, so the first one at or after `since` is bisected
+        let mut end = totals.count;
+        while first < end {
+            let mid = first + (end - first) / 2;
+            let checkpoint = RATE_CHECKPOINTS.load(deps.storage, mid % ACTIVITY_CAPACITY)?;
+            if checkpoint.time >= since {
+                end = mid;
+            } else {
+                first = mid + 1;
+            }
+        }
+    }
+    let before = if first < totals.count {
+        RATE_CHECKPOINTS
+            .load(deps.storage, first % ACTIVITY_CAPACITY)?
+            .before
+    } else {
+        totals.clone()
+    };
+
+    let amount_in = totals.amount_in - before.amount_in;
+    let amount_out = totals.amount_out - before.amount_out;
+    let nominal_out = get_conversion_output(&pair, amount_in)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let rate = if nominal_out.is_zero() {
+        Decimal::one()
+    } else {
+        Decimal::from_ratio(amount_out, nominal_out)
+    };
+    Ok(AverageRateResponse {
+        conversions: (totals.count - first) as u32,
+        amount_in,
+        amount_out,
+        rate,
+    })
+}
+
+fn query_export_config(deps: Deps) -> StdResult<ExportedConfig> {
+    let state = STATE.load(deps.storage)?;
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let hooks = HOOKS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let vaults = VAULTS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let roles = ROLES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            item.map(|(address, roles)| RoleGrant {
+                address: address.into(),
+                roles,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ExportedConfig {
+        dest_ic20_denom: pair.dest_ic20_denom,
+        dest_ic20_decimals: pair.dest_ic20_decimals,
+        src_ic20_denom: pair.src_ic20_denom,
+        src_ic20_decimals: pair.src_ic20_decimals,
+        ownership_transfer_delay: state.ownership_transfer_delay,
+        hooks: hooks.into_iter().map(String::from).collect(),
+        vaults: vaults.into_iter().map(String::from).collect(),
+        compliance_registry: COMPLIANCE_REGISTRY
+            .may_load(deps.storage)?
+            .map(String::from),
+        roles,
+        src_paths: SRC_PATHS.may_load(deps.storage)?.unwrap_or_default(),
+        receipts: RECEIPTS.may_load(deps.storage)?,
+        metadata: METADATA.may_load(deps.storage)?,
+        reserve_thresholds: RESERVE_THRESHOLDS
+            .may_load(deps.storage)?
+            .unwrap_or_default(),
+        subsidy_bonus_bps: SUBSIDY
+            .may_load(deps.storage)?
+            .unwrap_or_default()
+            .bonus_bps,
+        emission: EMISSION.may_load(deps.storage)?,
+    })
+}
+
+fn query_offers(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<OffersResponse> {
+    let offers = OFFERS
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .filter(|item| match item {
+            Ok((_, offer)) => {
+                !offer.expiration.is_expired(&env.block) && offer.used < offer.total_cap
+            }
+            Err(_) => true,
+        })
+        .take(clamp_limit(limit))
+        .map(|item| item.map(|(id, offer)| OfferInfo { id, offer }))
+        .collect::<StdResult<_>>()?;
+    Ok(OffersResponse { offers })
+}
+
+fn query_audit(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AuditResponse> {
+    let entries = LEDGER
+        .range(
+            deps.storage,
+            start_after.as_deref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(clamp_limit(limit))
+        .collect::<StdResult<Vec<_>>>()?;
+    let denoms = entries
+        .into_iter()
+        .map(|(denom, entry)| {
+            let balance = deps
+                .querier
+                .query_balance(&env.contract.address, &denom)?
+                .amount;
+            // compare without going negative: balance + outflows should equal inflows
+            let accounted = balance + entry.outflows;
+            Ok(AuditEntry {
+                denom,
+                inflows: entry.inflows,
+                outflows: entry.outflows,
+                balance,
+                surplus: accounted.saturating_sub(entry.inflows),
+                deficit: entry.inflows.saturating_sub(accounted),
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(AuditResponse { denoms })
+}
+
+fn query_reserve_status(deps: Deps, env: Env) -> StdResult<ReserveStatusResponse> {
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let reserve = deps
+        .querier
+        .query_balance(env.contract.address, &pair.dest_ic20_denom)?
+        .amount;
+    let thresholds = RESERVE_THRESHOLDS
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|threshold| ThresholdStatus {
+            threshold,
+            shortfall: threshold.saturating_sub(reserve),
+        })
+        .collect();
+    Ok(ReserveStatusResponse {
+        reserve,
+        thresholds,
+    })
+}
+
+fn query_src_paths(deps: Deps) -> StdResult<SrcPathsResponse> {
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let mut denoms = vec![pair.src_ic20_denom];
+    denoms.extend(SRC_PATHS.may_load(deps.storage)?.unwrap_or_default());
+    let paths = denoms
+        .into_iter()
+        .map(|denom| {
+            let converted = PATH_VOLUME
+                .may_load(deps.storage, &denom)?
+                .unwrap_or_default();
+            Ok(SrcPath { denom, converted })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(SrcPathsResponse { paths })
+}
+
+/// Prices a conversion with the same helpers the conversion itself settles with.
+pub(crate) fn query_quote(
+    deps: Deps,
+    env: Env,
+    amount_in: Uint128,
+    sender: Option<String>,
+) -> StdResult<QuoteResponse> {
+    let sender = sender
+        .map(|sender| deps.api.addr_validate(&sender))
+        .transpose()?;
+    ensure_quotable(deps, sender.as_ref(), amount_in)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let subsidy = SUBSIDY.may_load(deps.storage)?.unwrap_or_default();
+    let gross_amount_out = get_conversion_output(&pair, amount_in)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let subsidy_bonus = get_subsidy_bonus(&subsidy, gross_amount_out)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let offer = match &sender {
+        Some(sender) => find_best_offer(deps.storage, &env, sender, gross_amount_out)?,
+        None => None,
+    };
+    let promo_bonus = match offer {
+        Some((_, offer)) => gross_amount_out.multiply_ratio(offer.rate_bonus_bps, TOTAL_BPS),
+        None => Uint128::zero(),
+    };
+    let amount_out = gross_amount_out + subsidy_bonus + promo_bonus;
+    let effective_rate = if gross_amount_out.is_zero() {
+        Decimal::one()
+    } else {
+        Decimal::from_ratio(amount_out, gross_amount_out)
+    };
+    let max_amount_in =
+        query_max_convertible(deps, env, Direction::SrcToDest, sender.map(String::from))?.amount;
+    Ok(QuoteResponse {
+        amount_in,
+        gross_amount_out,
+        subsidy_bonus,
+        promo_bonus,
+        amount_out,
+        effective_rate,
+        max_amount_in,
+    })
+}
+
+/// Refuses to price what a conversion would refuse before settling anything.
+fn ensure_quotable(
+    deps: Deps,
+    sender: Option<&Addr>,
+    amount_in: Uint128,
+) -> Result<(), ContractError> {
+    match MODE.may_load(deps.storage)?.unwrap_or(Mode::Active) {
+        Mode::Active => {}
+        Mode::Paused => return Err(ContractError::Paused {}),
+        Mode::Deprecated => return Err(ContractError::Deprecated {}),
+        Mode::Closed => return Err(ContractError::Closed {}),
+    }
+    if amount_in.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    if let Some(sender) = sender {
+        ensure_allowed(deps, sender)?;
+    }
+    Ok(())
+}
+
+fn query_simulate_batch(
+    deps: Deps,
+    env: Env,
+    inputs: Vec<Uint128>,
+    sender: Option<String>,
+) -> StdResult<SimulateBatchResponse> {
+    if inputs.len() > MAX_SIMULATED_INPUTS {
+        return Err(StdError::generic_err(format!(
+            "Cannot simulate more than {} conversions at once",
+            MAX_SIMULATED_INPUTS
+        )));
+    }
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let sender = sender
+        .map(|sender| deps.api.addr_validate(&sender))
+        .transpose()?;
+    let mode_error = match MODE.may_load(deps.storage)?.unwrap_or(Mode::Active) {
+        Mode::Active => None,
+        Mode::Paused => Some(ContractError::Paused {}.to_string()),
+        Mode::Deprecated => Some(ContractError::Deprecated {}.to_string()),
+        Mode::Closed => Some(ContractError::Closed {}.to_string()),
+    };
+
+    // settle on a scratch copy of the state conversions use up, so that every conversion
+    // sees what the previous ones left
+    let mut scratch = MemoryStorage::new();
+    if let Some(subsidy) = SUBSIDY.may_load(deps.storage)? {
+        SUBSIDY.save(&mut scratch, &subsidy)?;
+    }
+    if let Some(sender) = &sender {
+        let offers = OFFERS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (id, offer) in offers {
+            OFFERS.save(&mut scratch, id, &offer)?;
+            if let Some(used) = OFFER_USAGE.may_load(deps.storage, (id, sender))? {
+                OFFER_USAGE.save(&mut scratch, (id, sender), &used)?;
+            }
+        }
+    }
+    let mut reserve = deps
+        .querier
+        .query_balance(&env.contract.address, &pair.dest_ic20_denom)?
+        .amount;
+
+    let results = inputs
+        .into_iter()
+        .map(|amount_in| {
+            let res = match &mode_error {
+                Some(err) => Err(err.clone()),
+                None if amount_in.is_zero() => Err(ContractError::InvalidZeroAmount {}.to_string()),
+                None => get_conversion_output(&pair, amount_in)
+                    .and_then(|gross_amount_out| {
+                        simulate_output(
+                            &mut scratch,
+                            &env,
+                            sender.as_ref(),
+                            gross_amount_out,
+                            reserve,
+                        )
+                    })
+                    .map_err(|err| err.to_string()),
+            };
+            match res {
+                Ok(amount_out) => {
+                    reserve -= amount_out;
+                    SimulatedConversion {
+                        amount_in,
+                        amount_out,
+                        error: None,
+                    }
+                }
+                Err(err) => SimulatedConversion {
+                    amount_in,
+                    amount_out: Uint128::zero(),
+                    error: Some(err),
+                },
+            }
+        })
+        .collect();
+    Ok(SimulateBatchResponse { results })
+}
+
+/// Settles the bonuses of one simulated conversion on `scratch` and returns its output. A
+/// conversion that would fail leaves `scratch` untouched.
+fn simulate_output(
+    scratch: &mut dyn Storage,
+    env: &Env,
+    sender: Option<&Addr>,
+    gross_amount_out: Uint128,
+    reserve: Uint128,
+) -> Result<Uint128, ContractError> {
+    let subsidy = SUBSIDY.may_load(scratch)?.unwrap_or_default();
+    let subsidy_bonus = get_subsidy_bonus(&subsidy, gross_amount_out)?;
+    let promo_bonus = match sender {
+        Some(sender) => match find_best_offer(scratch, env, sender, gross_amount_out)? {
+            Some((_, offer)) => gross_amount_out.multiply_ratio(offer.rate_bonus_bps, TOTAL_BPS),
+            None => Uint128::zero(),
+        },
+        None => Uint128::zero(),
+    };
+    let amount_out = gross_amount_out + subsidy_bonus + promo_bonus;
+    if amount_out > reserve {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    if let Some(sender) = sender {
+        apply_offer(scratch, env, sender, gross_amount_out)?;
+    }
+    apply_subsidy(scratch, gross_amount_out)?;
+    Ok(amount_out)
+}
+
+/// Converting is bounded by the same partial fill a conversion settles with: the reserve has to
+/// cover the subsidy and offer bonuses too. Converting back is bounded by the escrow and, for an
+/// address, by what it may return.
+fn query_max_convertible(
+    deps: Deps,
+    env: Env,
+    direction: Direction,
+    address: Option<String>,
+) -> StdResult<MaxConvertibleResponse> {
+    let address = address
+        .map(|address| deps.api.addr_validate(&address))
+        .transpose()?;
+    // converting back stays open while deprecated, converting does not
+    let open = matches!(
+        (
+            MODE.may_load(deps.storage)?.unwrap_or(Mode::Active),
+            &direction
+        ),
+        (Mode::Active, _) | (Mode::Deprecated, Direction::DestToSrc)
+    );
+    if !open {
+        return Ok(MaxConvertibleResponse {
+            amount: Uint128::zero(),
+        });
+    }
+    let pair = PAIR_CONFIG.load(deps.storage)?;
+    let amount = match direction {
+        Direction::SrcToDest => {
+            let reserve = deps
+                .querier
+                .query_balance(&env.contract.address, &pair.dest_ic20_denom)?
+                .amount;
+            // the whole reserve without any bonus is an upper bound for the fill, and one that
+            // cannot be represented in source units is no bound at all
+            let amount_in = get_reverse_conversion_output(&pair, reserve).unwrap_or(Uint128::MAX);
+            get_partial_fill(deps, &env, &pair, address.as_ref(), amount_in)
+                .map_err(|err| StdError::generic_err(err.to_string()))?
+        }
+        Direction::DestToSrc => {
+            let escrow = deps
+                .querier
+                .query_balance(&env.contract.address, &pair.src_ic20_denom)?
+                .amount;
+            let max_in = get_conversion_output(&pair, escrow)
+                .map_err(|err| StdError::generic_err(err.to_string()))?;
+            match address {
+                Some(address) => {
+                    let round_trip = ROUND_TRIPS
+                        .may_load(deps.storage, &address)?
+                        .unwrap_or_default();
+                    let until = round_trip.last_conversion.plus_seconds(ROUND_TRIP_COOLDOWN);
+                    if env.block.time < until {
+                        Uint128::zero()
+                    } else {
+                        max_in.min(round_trip.returnable)
+                    }
+                }
+                None => max_in,
+            }
+        }
+    };
+    Ok(MaxConvertibleResponse { amount })
+}
+
+fn query_export_state(
+    deps: Deps,
+    section: StateSection,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ExportStateResponse> {
+    let limit = clamp_limit(limit);
+    let entries = match section {
+        StateSection::Activity => {
+            let start = match start_after {
+                Some(slot) => Some(Bound::exclusive(slot.parse::<u64>().map_err(|_| {
+                    StdError::parse_err("u64", format!("invalid cursor {}", slot))
+                })?)),
+                None => None,
+            };
+            ACTIVITY
+                .range(deps.storage, start, None, Order::Ascending)
+                .take(limit)
+                .map(|item| item.map(|(slot, entry)| StateEntry::Activity { slot, entry }))
+                .collect::<StdResult<_>>()?
+        }
+        StateSection::Points => {
+            let start_after = start_after
+                .map(|addr| deps.api.addr_validate(&addr))
+                .transpose()?;
+            POINTS
+                .range(
+                    deps.storage,
+                    start_after.as_ref().map(Bound::exclusive),
+                    None,
+                    Order::Ascending,
+                )
+                .take(limit)
+                .map(|item| {
+                    item.map(|(address, points)| StateEntry::Points {
+                        address: address.into(),
+                        points,
+                    })
+                })
+                .collect::<StdResult<_>>()?
+        }
+        StateSection::Deposits => {
+            let start_after = start_after
+                .map(|addr| deps.api.addr_validate(&addr))
+                .transpose()?;
+            let generation = DEPOSIT_GENERATION
+                .may_load(deps.storage)?
+                .unwrap_or_default();
+            DEPOSITS
+                .prefix(generation)
+                .range(
+                    deps.storage,
+                    start_after.as_ref().map(Bound::exclusive),
+                    None,
+                    Order::Ascending,
+                )
+                .take(limit)
+                .map(|item| {
+                    item.map(|(address, amount)| StateEntry::Deposits {
+                        address: address.into(),
+                        amount,
+                    })
+                })
+                .collect::<StdResult<_>>()?
+        }
+        StateSection::Pair if start_after.is_none() => vec![StateEntry::Pair {
+            pair: PAIR_CONFIG.load(deps.storage)?,
+        }],
+        StateSection::Pair => vec![],
+    };
+    Ok(ExportStateResponse { entries })
+}
+
+fn query_mode(deps: Deps) -> StdResult<ModeResponse> {
+    let mode = MODE.may_load(deps.storage)?.unwrap_or(Mode::Active);
+    Ok(ModeResponse { mode })
+}
+
+fn query_pending_ownership(deps: Deps) -> StdResult<PendingOwnershipResponse> {
+    let pending = PENDING_OWNERSHIP.may_load(deps.storage)?;
+    Ok(PendingOwnershipResponse { pending })
+}
+
+fn query_roles(deps: Deps, address: String) -> StdResult<RolesResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let roles = ROLES.may_load(deps.storage, &address)?.unwrap_or_default();
+    Ok(RolesResponse { roles })
+}
+
+fn query_subsidy(deps: Deps) -> StdResult<SubsidyResponse> {
+    let subsidy = SUBSIDY.may_load(deps.storage)?.unwrap_or_default();
+    Ok(SubsidyResponse {
+        bonus_bps: subsidy.bonus_bps,
+        pool: subsidy.pool,
+    })
+}
+
+fn query_emission(deps: Deps) -> StdResult<EmissionResponse> {
+    let emission = EMISSION.may_load(deps.storage)?;
+    let pool = match &emission {
+        Some(emission) => REWARD_POOL
+            .may_load(deps.storage, &emission.denom)?
+            .unwrap_or_default(),
+        None => Uint128::zero(),
+    };
+    Ok(EmissionResponse { emission, pool })
+}
+
+fn query_rewards(
+    deps: Deps,
+    address: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<RewardsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let rewards = REWARDS
+        .prefix(&address)
+        .range(
+            deps.storage,
+            start_after.as_deref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(clamp_limit(limit))
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<_>>()?;
+    Ok(RewardsResponse { rewards })
+}
+
+fn query_deposit(deps: Deps, address: String) -> StdResult<DepositResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let generation = DEPOSIT_GENERATION
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let amount = DEPOSITS
+        .may_load(deps.storage, (generation, &address))?
+        .unwrap_or_default();
+    Ok(DepositResponse { amount })
+}
+
+fn query_points(deps: Deps, address: String) -> StdResult<PointsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let points = POINTS.may_load(deps.storage, &address)?.unwrap_or_default();
+    Ok(PointsResponse { points })
+}
+
+fn query_vaults(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<VaultsResponse> {
+    let start = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let vaults = VAULTS
+        .keys(
+            deps.storage,
+            start.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(clamp_limit(limit))
+        .map(|item| item.map(String::from))
+        .collect::<StdResult<_>>()?;
+    Ok(VaultsResponse { vaults })
+}
+
+fn query_hooks(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<HooksResponse> {
+    let start = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let hooks = HOOKS
+        .keys(
+            deps.storage,
+            start.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(clamp_limit(limit))
+        .map(|item| item.map(String::from))
+        .collect::<StdResult<_>>()?;
+    Ok(HooksResponse { hooks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::ConvertTokenResponse;
+    use crate::pagination::MAX_LIMIT;
+    use cosmwasm_std::testing::{
+        mock_dependencies_with_balance, mock_env, mock_info, MOCK_CONTRACT_ADDR,
+    };
+    use cosmwasm_std::{
+        attr, coin, coins, from_binary, ContractResult, Decimal, SubMsgResponse, SubMsgResult,
+        SystemError, SystemResult, WasmMsg,
+    };
+    use cw20::Expiration;
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &coins(1000, "earth"));
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // it worked, let's query the state
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+        let value: CountResponse = from_binary(&res).unwrap();
+        assert_eq!(17, value.count);
+
+        // payloads written before the newer fields existed still parse
+        let legacy = br#"{"count":17,"dest_ic20_denom":"cosmostoken","dest_ic20_decimals":6,"src_ic20_denom":"erc20token","src_ic20_decimals":18}"#;
+        let msg: InstantiateMsg = from_binary(&Binary::from(legacy.to_vec())).unwrap();
+        assert_eq!(0, msg.ownership_transfer_delay);
+        assert!(!msg.start_paused);
+    }
+
+    #[test]
+    fn scaling_factors_are_precomputed() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        let pair = PAIR_CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(Uint128::new(1_000_000), pair.dest_whole_token);
+        assert_eq!(
+            Uint128::new(1_000_000_000_000_000_000),
+            pair.src_whole_token
+        );
+
+        // a whole token that does not fit in a u128 is refused up front
+        let msg = InstantiateMsg {
+            src_ic20_decimals: 39,
+            ..msg
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::InvalidDecimals { max: 38 }) => {}
+            _ => panic!("Must return invalid decimals error"),
+        }
+    }
+
+    #[test]
+    fn increment() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // beneficiary can release it
+        let info = mock_info("anyone", &coins(2, "token"));
+        let msg = ExecuteMsg::Increment {};
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // should increase counter by 1
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+        let value: CountResponse = from_binary(&res).unwrap();
+        assert_eq!(18, value.count);
+    }
+
+    #[test]
+    fn reset() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
         // beneficiary can release it
         let unauth_info = mock_info("anyone", &coins(2, "token"));
         let msg = ExecuteMsg::Reset { count: 5 };
-        let res = execute(deps.as_mut(), mock_env(), unauth_info, msg);
+        let res = execute(deps.as_mut(), mock_env(), unauth_info, msg);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // only the original creator can reset the counter
+        let auth_info = mock_info("creator", &coins(2, "token"));
+        let msg = ExecuteMsg::Reset { count: 5 };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        // should now be 5
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+        let value: CountResponse = from_binary(&res).unwrap();
+        assert_eq!(5, value.count);
+    }
+    #[test]
+    fn hooks() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the owner can register hooks
+        let unauth_info = mock_info("anyone", &[]);
+        let msg = ExecuteMsg::AddHook {
+            addr: "rewards".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), unauth_info, msg.clone());
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        let auth_info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg);
+        match res {
+            Err(ContractError::HookAlreadyRegistered { .. }) => {}
+            _ => panic!("Must return hook already registered error"),
+        }
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Hooks {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: HooksResponse = from_binary(&res).unwrap();
+        assert_eq!(vec!["rewards".to_string()], value.hooks);
+
+        // every conversion notifies the registered hooks, failures are only logged
+        let convert_info = mock_info("creator", &coins(1_000_000_000_000_000, "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &convert_info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(2, res.messages.len());
+        let hook_msg = ConversionHookMsg {
+            id: 0,
+            sender: "creator".to_string(),
+            src_amount: Uint128::new(1_000_000_000_000_000),
+            dest_amount: Uint128::new(1_000),
+        };
+        assert_eq!(
+            SubMsg::reply_on_error(hook_msg.into_cosmos_msg("rewards").unwrap(), HOOK_REPLY_ID)
+                .with_gas_limit(HOOK_GAS_LIMIT),
+            res.messages[1]
+        );
+        let data: ConvertResultResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(0, data.id);
+
+        let msg = ExecuteMsg::RemoveHook {
+            addr: "rewards".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), auth_info, msg);
+        match res {
+            Err(ContractError::HookNotRegistered { .. }) => {}
+            _ => panic!("Must return hook not registered error"),
+        }
+
+        // the number of hooks notified on every conversion is bounded
+        let auth_info = mock_info("creator", &[]);
+        for i in 0..MAX_HOOKS {
+            let msg = ExecuteMsg::AddHook {
+                addr: format!("hook{}", i),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        }
+        let msg = ExecuteMsg::AddHook {
+            addr: "onemore".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info, msg);
+        match res {
+            Err(ContractError::TooManyHooks { max }) => assert_eq!(MAX_HOOKS, max),
+            _ => panic!("Must return too many hooks error"),
+        }
+    }
+
+    #[test]
+    fn roles() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // without the role, managing hooks is not allowed
+        let manager_info = mock_info("manager", &[]);
+        let add_hook = ExecuteMsg::AddHook {
+            addr: "rewards".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            manager_info.clone(),
+            add_hook.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // only the owner can grant roles
+        let grant = ExecuteMsg::GrantRole {
+            addr: "manager".to_string(),
+            role: Role::HookManager,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            manager_info.clone(),
+            grant.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let auth_info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), grant).unwrap();
+
+        let msg = QueryMsg::Roles {
+            address: "manager".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: RolesResponse = from_binary(&res).unwrap();
+        assert_eq!(vec![Role::HookManager], value.roles);
+
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            manager_info.clone(),
+            add_hook.clone(),
+        )
+        .unwrap();
+
+        // revoking takes the privilege away again
+        let revoke = ExecuteMsg::RevokeRole {
+            addr: "manager".to_string(),
+            role: Role::HookManager,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), revoke.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), auth_info, revoke);
+        match res {
+            Err(ContractError::RoleNotGranted {}) => {}
+            _ => panic!("Must return role not granted error"),
+        }
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: RolesResponse = from_binary(&res).unwrap();
+        assert!(value.roles.is_empty());
+        let remove_hook = ExecuteMsg::RemoveHook {
+            addr: "rewards".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), manager_info, remove_hook);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+    }
+
+    #[test]
+    fn ownership_transfer() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let auth_info = mock_info("creator", &[]);
+        let new_owner_info = mock_info("dao", &[]);
+        let propose = ExecuteMsg::ProposeOwner {
+            new_owner: "dao".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            new_owner_info.clone(),
+            propose.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            auth_info.clone(),
+            propose.clone(),
+        )
+        .unwrap();
+
+        // the current owner can still back out during the delay
+        let msg = ExecuteMsg::CancelOwnershipTransfer {};
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        assert_eq!("ownership_transfer_cancelled", res.events[0].ty);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::PendingOwnership {}).unwrap();
+        let value: PendingOwnershipResponse = from_binary(&res).unwrap();
+        assert_eq!(None, value.pending);
+
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, propose).unwrap();
+
+        // too early
+        let msg = ExecuteMsg::AcceptOwnership {};
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            new_owner_info.clone(),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::OwnershipTransferLocked { .. }) => {}
+            _ => panic!("Must return ownership transfer locked error"),
+        }
+
+        // only the proposed owner can accept
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(3600);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), env, new_owner_info, msg).unwrap();
+
+        // the new owner now holds the owner privileges
+        let auth_info = mock_info("dao", &[]);
+        let msg = ExecuteMsg::Reset { count: 5 };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::PendingOwnership {}).unwrap();
+        let value: PendingOwnershipResponse = from_binary(&res).unwrap();
+        assert_eq!(None, value.pending);
+    }
+
+    #[test]
+    fn compliance_registry() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } => {
+                assert_eq!("registry", contract_addr);
+                let ComplianceQueryMsg::IsAllowed { address } = from_binary(msg).unwrap();
+                let res = IsAllowedResponse {
+                    allowed: address == "verified",
+                };
+                SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+            }
+            _ => panic!("Unexpected query"),
+        });
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::SetComplianceRegistry {
+            registry: Some("registry".to_string()),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let info = mock_info("anonymous", &coins(1_000_000_000_000_000, "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        );
+        match res {
+            Err(ContractError::AddressNotAllowed { address }) => assert_eq!("anonymous", address),
+            _ => panic!("Must return address not allowed error"),
+        }
+        let quote_msg = QueryMsg::Quote {
+            amount_in: Uint128::new(1_000_000_000_000_000),
+            sender: Some("anonymous".to_string()),
+        };
+        let _err = query(deps.as_ref(), mock_env(), quote_msg).unwrap_err();
+        let info = mock_info("verified", &coins(1_000_000_000_000_000, "erc20token"));
+        let _res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+
+        // clearing the registry opens conversions to everyone again
+        let msg = ExecuteMsg::SetComplianceRegistry { registry: None };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let info = mock_info("anonymous", &coins(1_000_000_000_000_000, "erc20token"));
+        let _res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn dao_handover() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::ContractInfo { contract_addr } if contract_addr == "dao" => {
+                let info = br#"{"code_id":1,"creator":"creator","pinned":false}"#;
+                SystemResult::Ok(ContractResult::Ok(Binary::from(info.as_ref())))
+            }
+            WasmQuery::ContractInfo { contract_addr } => {
+                SystemResult::Err(SystemError::NoSuchContract {
+                    addr: contract_addr.clone(),
+                })
+            }
+            _ => panic!("Unexpected query"),
+        });
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 0,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // a typo'd or externally owned address is refused
+        let auth_info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::ProposeDaoOwner {
+            dao: "not-a-dao".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg);
+        match res {
+            Err(ContractError::NotAContract { addr }) => assert_eq!("not-a-dao", addr),
+            _ => panic!("Must return not a contract error"),
+        }
+
+        let msg = ExecuteMsg::ProposeDaoOwner {
+            dao: "dao".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        // ownership only moves once the DAO acknowledges it
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::PendingOwnership {}).unwrap();
+        let value: PendingOwnershipResponse = from_binary(&res).unwrap();
+        assert_eq!(Addr::unchecked("dao"), value.pending.unwrap().new_owner);
+        let msg = ExecuteMsg::AcceptOwnership {};
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("dao", &[]), msg).unwrap();
+        let state = STATE.load(deps.as_ref().storage).unwrap();
+        assert_eq!(Addr::unchecked("dao"), state.owner);
+    }
+
+    #[test]
+    fn failed_hook_is_only_logged() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let reply_msg = Reply {
+            id: HOOK_REPLY_ID,
+            result: SubMsgResult::Err("hook exploded".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        assert_eq!("hook exploded", res.attributes[1].value);
+
+        let reply_msg = Reply {
+            id: 42,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+        let res = reply(deps.as_mut(), mock_env(), reply_msg);
+        match res {
+            Err(ContractError::UnknownReplyId { id: 42 }) => {}
+            _ => panic!("Must return unknown reply id error"),
+        }
+    }
+
+    #[test]
+    fn convert_with_callback() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("anyone", &coins(1_000_000_000_000_000, "erc20token"));
+        let callback = Callback {
+            contract: "vault".to_string(),
+            msg: Binary::from(b"{}".to_vec()),
+            funds_policy: FundsPolicy::Attach,
+        };
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions {
+                callback: Some(callback.clone()),
+                ..ConvertOptions::default()
+            },
+        )
+        .unwrap();
+        // the converted funds go straight to the callback, wrapped in a fixed envelope
+        assert_eq!(1, res.messages.len());
+        let receive = ReceiveConversionMsg {
+            sender: "anyone".to_string(),
+            amount_out: Uint128::new(1_000),
+            id: 0,
+            msg: callback.msg.clone(),
+        };
+        assert_eq!(
+            receive
+                .into_cosmos_msg("vault", coins(1_000, "cosmostoken"))
+                .unwrap(),
+            res.messages[0].msg
+        );
+
+        let callback = Callback {
+            funds_policy: FundsPolicy::SendToSender,
+            ..callback
+        };
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions {
+                callback: Some(callback.clone()),
+                ..ConvertOptions::default()
+            },
+        )
+        .unwrap();
+        // the sender is paid and the callback runs without funds
+        assert_eq!(2, res.messages.len());
+        assert_eq!(
+            get_bank_transfer_to_msg(&info.sender, "cosmostoken", Uint128::new(1_000)),
+            res.messages[0].msg
+        );
+        let receive = ReceiveConversionMsg {
+            sender: "anyone".to_string(),
+            amount_out: Uint128::new(1_000),
+            id: 1,
+            msg: callback.msg.clone(),
+        };
+        assert_eq!(
+            receive.into_cosmos_msg("vault", vec![]).unwrap(),
+            res.messages[1].msg
+        );
+
+        // hooks, vaults, the receipt contract and the converter itself cannot be called back
+        let auth_info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddHook {
+            addr: "rewards".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        for target in ["rewards", MOCK_CONTRACT_ADDR].iter() {
+            let callback = Callback {
+                contract: target.to_string(),
+                ..callback.clone()
+            };
+            let res = convert_tokens(
+                deps.as_mut(),
+                &info,
+                mock_env(),
+                None,
+                ConvertOptions {
+                    callback: Some(callback),
+                    ..ConvertOptions::default()
+                },
+            );
+            match res {
+                Err(ContractError::InvalidCallbackTarget { addr }) => assert_eq!(*target, addr),
+                _ => panic!("Must return invalid callback target error"),
+            }
+        }
+    }
+
+    #[test]
+    fn convert_with_splits() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 6,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("treasury", &coins(1_000, "erc20token"));
+        let splits = vec![
+            Split {
+                recipient: "grants".to_string(),
+                bps: 3_000,
+            },
+            Split {
+                recipient: "ops".to_string(),
+                bps: 3_000,
+            },
+        ];
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions {
+                splits: Some(splits),
+                ..ConvertOptions::default()
+            },
+        );
+        match res {
+            Err(ContractError::InvalidSplits {}) => {}
+            _ => panic!("Must return invalid splits error"),
+        }
+
+        let amount = Uint128::new(1_000);
+        let splits = vec![
+            Split {
+                recipient: "grants".to_string(),
+                bps: 3_333,
+            },
+            Split {
+                recipient: "ops".to_string(),
+                bps: 6_667,
+            },
+        ];
+        let msgs = get_split_transfer_msgs(deps.as_ref(), &splits, "cosmostoken", amount).unwrap();
+        assert_eq!(
+            vec![
+                get_bank_transfer_to_msg(&Addr::unchecked("grants"), "cosmostoken", 333u128.into()),
+                get_bank_transfer_to_msg(&Addr::unchecked("ops"), "cosmostoken", 667u128.into()),
+            ],
+            msgs
+        );
+
+        // split funds cannot also be attached to a callback
+        let callback = Callback {
+            contract: "vault".to_string(),
+            msg: Binary::from(b"{}".to_vec()),
+            funds_policy: FundsPolicy::Attach,
+        };
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions {
+                callback: Some(callback),
+                splits: Some(splits),
+                ..ConvertOptions::default()
+            },
+        );
+        match res {
+            Err(ContractError::ConflictingPayout {}) => {}
+            _ => panic!("Must return conflicting payout error"),
+        }
+    }
+
+    #[test]
+    fn convert_into_vault() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("anyone", &coins(1_000_000_000_000_000, "erc20token"));
+        let vault_deposit = VaultDeposit {
+            vault: "vault".to_string(),
+            recipient: None,
+        };
+        let options = ConvertOptions {
+            vault_deposit: Some(vault_deposit.clone()),
+            ..ConvertOptions::default()
+        };
+
+        // only whitelisted vaults can be deposited into
+        let res = convert_tokens(deps.as_mut(), &info, mock_env(), None, options.clone());
+        match res {
+            Err(ContractError::VaultNotRegistered { .. }) => {}
+            _ => panic!("Must return vault not registered error"),
+        }
+
+        let msg = ExecuteMsg::AddVault {
+            addr: "vault".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone());
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Vaults {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: VaultsResponse = from_binary(&res).unwrap();
+        assert_eq!(vec!["vault".to_string()], value.vaults);
+
+        let res = convert_tokens(deps.as_mut(), &info, mock_env(), None, options).unwrap();
+        // the deposit message is built by the converter, crediting the sender
+        assert_eq!(1, res.messages.len());
+        assert_eq!(
+            CosmosMsg::from(WasmMsg::Execute {
+                contract_addr: "vault".to_string(),
+                msg: Binary::from(br#"{"deposit":{"recipient":"anyone"}}"#.to_vec()),
+                funds: coins(1_000, "cosmostoken"),
+            }),
+            res.messages[0].msg
+        );
+
+        // the output can only go to one place
+        let options = ConvertOptions {
+            vault_deposit: Some(vault_deposit),
+            splits: Some(vec![Split {
+                recipient: "anyone".to_string(),
+                bps: 10_000,
+            }]),
+            ..ConvertOptions::default()
+        };
+        let res = convert_tokens(deps.as_mut(), &info, mock_env(), None, options);
+        match res {
+            Err(ContractError::ConflictingPayout {}) => {}
+            _ => panic!("Must return conflicting payout error"),
+        }
+    }
+
+    #[test]
+    fn subsidy() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the owner funds the pool, and only with the destination token
+        let msg = ExecuteMsg::FundSubsidy {};
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &coins(150, "cosmostoken")),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &coins(150, "erc20token")),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::InvalidFunds {}) => {}
+            _ => panic!("Must return invalid funds error"),
+        }
+        let auth_info = mock_info("creator", &coins(150, "cosmostoken"));
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        // no bonus until one is configured
+        let amount = apply_subsidy(deps.as_mut().storage, Uint128::new(1_000)).unwrap();
+        assert_eq!(Uint128::new(1_000), amount);
+
+        let msg = ExecuteMsg::SetSubsidyBonus { bonus_bps: 1_000 };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let amount = apply_subsidy(deps.as_mut().storage, Uint128::new(1_000)).unwrap();
+        assert_eq!(Uint128::new(1_100), amount);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Subsidy {}).unwrap();
+        let value: SubsidyResponse = from_binary(&res).unwrap();
+        assert_eq!(1_000, value.bonus_bps);
+        assert_eq!(Uint128::new(50), value.pool);
+
+        // conversions halt instead of going on unsubsidized
+        let res = apply_subsidy(deps.as_mut().storage, Uint128::new(1_000));
+        match res {
+            Err(ContractError::SubsidyExhausted {}) => {}
+            _ => panic!("Must return subsidy exhausted error"),
+        }
+    }
+
+    #[test]
+    fn loyalty_points() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = QueryMsg::Points {
+            address: "anyone".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: PointsResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::zero(), value.points);
+
+        // points add up over conversions
+        let anyone = Addr::unchecked("anyone");
+        accrue_points(deps.as_mut().storage, &anyone, Uint128::new(1_500)).unwrap();
+        accrue_points(deps.as_mut().storage, &anyone, Uint128::new(500)).unwrap();
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: PointsResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(2_000), value.points);
+    }
+
+    #[test]
+    fn emission_rewards() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let env = mock_env();
+        let emission = Emission {
+            denom: "reward".to_string(),
+            per_unit: Decimal::percent(50),
+            end: env.block.time.plus_seconds(100),
+        };
+        let msg = ExecuteMsg::SetEmission {
+            emission: Some(emission.clone()),
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+
+        // the rewards cannot be paid in a token the conversion holds
+        let msg = ExecuteMsg::SetEmission {
+            emission: Some(Emission {
+                denom: "cosmostoken".to_string(),
+                ..emission.clone()
+            }),
+        };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::InvalidEmissionDenom { denom }) => assert_eq!("cosmostoken", denom),
+            _ => panic!("Must return invalid emission denom error"),
+        }
+
+        let fund_info = mock_info("creator", &coins(300, "reward"));
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            fund_info,
+            ExecuteMsg::FundRewards {},
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Emission {}).unwrap();
+        let value: EmissionResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(emission), value.emission);
+        assert_eq!(Uint128::new(300), value.pool);
+
+        // rewards accrue until the emission ends
+        let anyone = Addr::unchecked("anyone");
+        accrue_rewards(deps.as_mut().storage, &env, &anyone, Uint128::new(1_000)).unwrap();
+        let mut ended = env.clone();
+        ended.block.time = ended.block.time.plus_seconds(100);
+        accrue_rewards(deps.as_mut().storage, &ended, &anyone, Uint128::new(1_000)).unwrap();
+
+        let msg = QueryMsg::Rewards {
+            address: "anyone".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), env.clone(), msg.clone()).unwrap();
+        let value: RewardsResponse = from_binary(&res).unwrap();
+        assert_eq!(coins(500, "reward"), value.rewards);
+
+        // claims are paid only as far as the pool goes, the rest stays owed
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "anyone".to_string(),
+                amount: coins(300, "reward"),
+            })],
+            res.messages
+        );
+        let res = query(deps.as_ref(), env.clone(), msg.clone()).unwrap();
+        let value: RewardsResponse = from_binary(&res).unwrap();
+        assert_eq!(coins(200, "reward"), value.rewards);
+
+        // claiming pays out and clears the accrued rewards
+        let fund_info = mock_info("creator", &coins(200, "reward"));
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            fund_info,
+            ExecuteMsg::FundRewards {},
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "anyone".to_string(),
+                amount: coins(200, "reward"),
+            })],
+            res.messages
+        );
+        let res = query(deps.as_ref(), env, msg).unwrap();
+        let value: RewardsResponse = from_binary(&res).unwrap();
+        assert!(value.rewards.is_empty());
+    }
+
+    #[test]
+    fn convert_whole_tokens() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // two whole source tokens buy two whole destination tokens
+        let amount = Uint128::new(2_000_000_000_000_000_000);
+        let info = mock_info("anyone", &coins(amount.u128(), "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            Some(amount),
+            ConvertOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            SubMsg::new(BankMsg::Send {
+                to_address: "anyone".to_string(),
+                amount: coins(2_000_000, "cosmostoken"),
+            }),
+            res.messages[0]
+        );
+
+        // the amount can be left out, everything attached is converted then
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            SubMsg::new(BankMsg::Send {
+                to_address: "anyone".to_string(),
+                amount: coins(2_000_000, "cosmostoken"),
+            }),
+            res.messages[0]
+        );
+
+        // extra coins or a mismatched amount are refused
+        let mut funds = coins(amount.u128(), "erc20token");
+        funds.push(Coin::new(5, "cosmostoken"));
+        let info = mock_info("anyone", &funds);
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            Some(amount),
+            ConvertOptions::default(),
+        );
+        match res {
+            Err(ContractError::InvalidFunds {}) => {}
+            _ => panic!("Must return invalid funds error"),
+        }
+        let info = mock_info("anyone", &coins(amount.u128(), "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            Some(Uint128::new(1)),
+            ConvertOptions::default(),
+        );
+        match res {
+            Err(ContractError::InvalidFunds {}) => {}
+            _ => panic!("Must return invalid funds error"),
+        }
+
+        // and so is a conversion of nothing
+        let info = mock_info("anyone", &[]);
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            Some(Uint128::zero()),
+            ConvertOptions::default(),
+        );
+        match res {
+            Err(ContractError::InvalidZeroAmount {}) => {}
+            _ => panic!("Must return invalid zero amount error"),
+        }
+    }
+
+    #[test]
+    fn conversion_commits_state_before_messages() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let owner_info = mock_info("creator", &coins(100, "cosmostoken"));
+        let msg = ExecuteMsg::AddHook {
+            addr: "rewards".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::FundSubsidy {};
+        let _res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetSubsidyBonus { bonus_bps: 1_000 };
+        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg).unwrap();
+
+        let amount = Uint128::new(1_000_000_000_000_000);
+        let info = mock_info("anyone", &coins(amount.u128(), "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            Some(amount),
+            ConvertOptions::default(),
+        )
+        .unwrap();
+
+        // the messages carry exactly what was committed to storage
+        let counters = COUNTERS.load(deps.as_ref().storage).unwrap();
+        assert_eq!(1, counters.conversions);
+        let entry = ACTIVITY.load(deps.as_ref().storage, 0).unwrap();
+        assert_eq!(Uint128::new(1_100), entry.amount_out);
+        assert_eq!(
+            SubMsg::new(BankMsg::Send {
+                to_address: "anyone".to_string(),
+                amount: coins(entry.amount_out.u128(), "cosmostoken"),
+            }),
+            res.messages[0]
+        );
+        let hook_msg = ConversionHookMsg {
+            id: counters.conversions - 1,
+            sender: "anyone".to_string(),
+            src_amount: amount,
+            dest_amount: entry.amount_out,
+        };
+        assert_eq!(
+            SubMsg::reply_on_error(hook_msg.into_cosmos_msg("rewards").unwrap(), HOOK_REPLY_ID)
+                .with_gas_limit(HOOK_GAS_LIMIT),
+            res.messages[1]
+        );
+        let points = POINTS
+            .load(deps.as_ref().storage, &Addr::unchecked("anyone"))
+            .unwrap();
+        assert_eq!(entry.amount_out, points);
+
+        // a conflicting payout is refused before anything is written
+        let options = ConvertOptions {
+            splits: Some(vec![]),
+            vault_deposit: Some(VaultDeposit {
+                vault: "vault".to_string(),
+                recipient: None,
+            }),
+            ..ConvertOptions::default()
+        };
+        let res = convert_tokens(deps.as_mut(), &info, mock_env(), Some(amount), options);
+        match res {
+            Err(ContractError::ConflictingPayout {}) => {}
+            _ => panic!("Must return conflicting payout error"),
+        }
+        let counters = COUNTERS.load(deps.as_ref().storage).unwrap();
+        assert_eq!(1, counters.conversions);
+    }
+
+    #[test]
+    fn deprecated_mode() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Mode {}).unwrap();
+        let value: ModeResponse = from_binary(&res).unwrap();
+        assert_eq!(Mode::Active, value.mode);
+
+        let msg = ExecuteMsg::SetMode {
+            mode: Mode::Deprecated,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // conversions stop, claims keep working
+        let info = mock_info("anyone", &coins(1_000_000_000_000_000, "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        );
+        match res {
+            Err(ContractError::Deprecated {}) => {}
+            _ => panic!("Must return deprecated error"),
+        }
+        let info = mock_info("anyone", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::ClaimRewards {}).unwrap();
+    }
+
+    #[test]
+    fn start_paused() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: true,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Mode {}).unwrap();
+        let value: ModeResponse = from_binary(&res).unwrap();
+        assert_eq!(Mode::Paused, value.mode);
+
+        // liquidity can be seeded, but nobody converts yet
+        let _res = deposit_dest_tokens(
+            deps.as_mut(),
+            &mock_info("creator", &coins(100, "cosmostoken")),
+            mock_env(),
+        )
+        .unwrap();
+        let anyone = mock_info("anyone", &coins(1_000_000_000_000_000, "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &anyone,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        );
+        match res {
+            Err(ContractError::Paused {}) => {}
+            _ => panic!("Must return paused error"),
+        }
+
+        let msg = ExecuteMsg::SetMode { mode: Mode::Active };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let _res = convert_tokens(
+            deps.as_mut(),
+            &anyone,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn import_records() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: true,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let record = ImportedRecord {
+            address: "anyone".to_string(),
+            amount_in: Uint128::new(1_000),
+            amount_out: Uint128::new(10),
+            height: 42,
+            time: Timestamp::from_seconds(1_600_000_000),
+            memo: None,
+        };
+        let msg = ExecuteMsg::ImportRecords {
+            records: vec![record.clone(); 2],
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+
+        let msg = QueryMsg::Points {
+            address: "anyone".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: PointsResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(20), value.points);
+        let counters = COUNTERS.load(deps.as_ref().storage).unwrap();
+        assert_eq!(2, counters.conversions);
+        assert_eq!(42, ACTIVITY.load(deps.as_ref().storage, 1).unwrap().height);
+
+        // batches are bounded
+        let msg = ExecuteMsg::ImportRecords {
+            records: vec![record.clone(); MAX_IMPORT_BATCH + 1],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        match res {
+            Err(ContractError::ImportBatchTooLarge { .. }) => {}
+            _ => panic!("Must return import batch too large error"),
+        }
+
+        // memos are held to the same length as live ones
+        let msg = ExecuteMsg::ImportRecords {
+            records: vec![ImportedRecord {
+                memo: Some("x".repeat(MAX_MEMO_LENGTH + 1)),
+                ..record.clone()
+            }],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        match res {
+            Err(ContractError::MemoTooLong { max }) => assert_eq!(MAX_MEMO_LENGTH, max),
+            _ => panic!("Must return memo too long error"),
+        }
+
+        // further batches are fine until the contract is used
+        let msg = ExecuteMsg::ImportRecords {
+            records: vec![record.clone()],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+
+        // and only accepted before going live
+        let mode_msg = ExecuteMsg::SetMode { mode: Mode::Active };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), mode_msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone());
+        match res {
+            Err(ContractError::NotPaused {}) => {}
+            _ => panic!("Must return not paused error"),
+        }
+
+        // pausing again after real activity does not reopen the import
+        let deposit_msg = ExecuteMsg::DepositLiquidity {};
+        let provider_info = mock_info("provider", &coins(100, "cosmostoken"));
+        let _res = execute(deps.as_mut(), mock_env(), provider_info, deposit_msg).unwrap();
+        let mode_msg = ExecuteMsg::SetMode { mode: Mode::Paused };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), mode_msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::AlreadyInUse {}) => {}
+            _ => panic!("Must return already in use error"),
+        }
+    }
+
+    #[test]
+    fn export_state() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        for address in ["alice", "bob", "carol"].iter() {
+            let address = Addr::unchecked(*address);
+            accrue_points(deps.as_mut().storage, &address, Uint128::new(5)).unwrap();
+        }
+
+        // pages follow on from the last key of the previous one
+        let msg = QueryMsg::ExportState {
+            section: StateSection::Points,
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ExportStateResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.entries.len());
+        let msg = QueryMsg::ExportState {
+            section: StateSection::Points,
+            start_after: Some("bob".to_string()),
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ExportStateResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![StateEntry::Points {
+                address: "carol".to_string(),
+                points: Uint128::new(5),
+            }],
+            value.entries
+        );
+
+        // deposits are exported the same way
+        for address in ["alice", "bob"].iter() {
+            let info = mock_info(address, &coins(10, "cosmostoken"));
+            let _res = deposit_dest_tokens(deps.as_mut(), &info, mock_env()).unwrap();
+        }
+        let msg = QueryMsg::ExportState {
+            section: StateSection::Deposits,
+            start_after: Some("alice".to_string()),
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ExportStateResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![StateEntry::Deposits {
+                address: "bob".to_string(),
+                amount: Uint128::new(10),
+            }],
+            value.entries
+        );
+
+        // address cursors are validated like the addresses themselves
+        let msg = QueryMsg::ExportState {
+            section: StateSection::Deposits,
+            start_after: Some("a".to_string()),
+            limit: None,
+        };
+        let _err = query(deps.as_ref(), mock_env(), msg).unwrap_err();
+
+        let msg = QueryMsg::ExportState {
+            section: StateSection::Pair,
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ExportStateResponse = from_binary(&res).unwrap();
+        match &value.entries[..] {
+            [StateEntry::Pair { pair }] => assert_eq!("erc20token", pair.src_ic20_denom),
+            _ => panic!("Must return the pair"),
+        }
+    }
+
+    #[test]
+    fn guardian_can_only_pause() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian", &[]),
+            ExecuteMsg::Pause {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let msg = ExecuteMsg::GrantRole {
+            addr: "guardian".to_string(),
+            role: Role::Guardian,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let guardian_info = mock_info("guardian", &[]);
+        let msg = ExecuteMsg::Pause {};
+        let _res = execute(deps.as_mut(), mock_env(), guardian_info.clone(), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Mode {}).unwrap();
+        let value: ModeResponse = from_binary(&res).unwrap();
+        assert_eq!(Mode::Paused, value.mode);
+
+        // resuming is up to the owner
+        let msg = ExecuteMsg::SetMode { mode: Mode::Active };
+        let res = execute(deps.as_mut(), mock_env(), guardian_info, msg);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+    }
+
+    #[test]
+    fn operator_and_incentive_manager_roles() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let offer_msg = ExecuteMsg::PostOffer {
+            rate_bonus_bps: 1_000,
+            per_user_cap: Uint128::new(1_000),
+            total_cap: Uint128::new(10_000),
+            expiration: Expiration::Never {},
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("marketing", &[]),
+            offer_msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        for (addr, role) in &[
+            ("marketing", Role::IncentiveManager),
+            ("operator", Role::Operator),
+        ] {
+            let msg = ExecuteMsg::GrantRole {
+                addr: addr.to_string(),
+                role: role.clone(),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        let marketing_info = mock_info("marketing", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), marketing_info.clone(), offer_msg).unwrap();
+        let metadata_msg = ExecuteMsg::SetMetadata {
+            metadata: Metadata {
+                name: "Bridge".to_string(),
+                description: "Converts bridged tokens".to_string(),
+                logo_uri: None,
+                docs_uri: None,
+            },
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            marketing_info,
+            metadata_msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        let operator_info = mock_info("operator", &[]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            operator_info.clone(),
+            metadata_msg,
+        )
+        .unwrap();
+        let msg = ExecuteMsg::AddVault {
+            addr: "vault".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), operator_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetSubsidyBonus { bonus_bps: 1_000 };
+        let res = execute(deps.as_mut(), mock_env(), operator_info, msg);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+    }
+
+    #[test]
+    fn max_convertible() {
+        let mut deps = mock_dependencies_with_balance(&coins(1_100, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = QueryMsg::MaxConvertible {
+            direction: Direction::SrcToDest,
+            address: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1_100_000_000_000_000), value.amount);
+
+        // with a 10% bonus the reserve covers 1000 out, plus 100 bonus from the pool
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &coins(500, "cosmostoken")),
+            ExecuteMsg::FundSubsidy {},
+        )
+        .unwrap();
+        let bonus_msg = ExecuteMsg::SetSubsidyBonus { bonus_bps: 1_000 };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), bonus_msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1_000_000_000_000_000), value.amount);
+
+        // an offer the address qualifies for is paid out of the same reserve, exactly as a
+        // partial fill would settle it
+        let offer_msg = ExecuteMsg::PostOffer {
+            rate_bonus_bps: 1_000,
+            per_user_cap: Uint128::new(1_000),
+            total_cap: Uint128::new(10_000),
+            expiration: Expiration::Never {},
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), offer_msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1_000_000_000_000_000), value.amount);
+        let for_anyone = QueryMsg::MaxConvertible {
+            direction: Direction::SrcToDest,
+            address: Some("anyone".to_string()),
+        };
+        let res = query(deps.as_ref(), mock_env(), for_anyone).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(916_000_000_000_000), value.amount);
+        let convert_info = mock_info("anyone", &coins(2_000_000_000_000_000, "erc20token"));
+        let options = ConvertOptions {
+            allow_partial: true,
+            ..ConvertOptions::default()
+        };
+        let res = convert_tokens(deps.as_mut(), &convert_info, mock_env(), None, options).unwrap();
+        let data: ConvertResultResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(
+            Uint128::new(2_000_000_000_000_000) - value.amount,
+            data.refunded
+        );
+
+        // nothing while paused
+        let pause_msg = ExecuteMsg::SetMode { mode: Mode::Paused };
+        let _res = execute(deps.as_mut(), mock_env(), info, pause_msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::zero(), value.amount);
+    }
+
+    #[test]
+    fn quote_matches_settlement() {
+        let mut deps = mock_dependencies_with_balance(&coins(10_000, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &coins(500, "cosmostoken")),
+            ExecuteMsg::FundSubsidy {},
+        )
+        .unwrap();
+        let bonus_msg = ExecuteMsg::SetSubsidyBonus { bonus_bps: 500 };
+        let _res = execute(deps.as_mut(), mock_env(), info, bonus_msg).unwrap();
+
+        let amount_in = Uint128::new(1_000_000_000_000_000);
+        let msg = QueryMsg::Quote {
+            amount_in,
+            sender: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let quote: QuoteResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1_000), quote.gross_amount_out);
+        assert_eq!(Uint128::new(50), quote.subsidy_bonus);
+        assert_eq!(Decimal::percent(105), quote.effective_rate);
+
+        let info = mock_info("anyone", &coins(amount_in.u128(), "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+        let data: ConvertResultResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(quote.amount_out, data.amount_out);
+
+        // nothing is quoted that would not settle
+        let zero_msg = QueryMsg::Quote {
+            amount_in: Uint128::zero(),
+            sender: None,
+        };
+        let _err = query(deps.as_ref(), mock_env(), zero_msg).unwrap_err();
+        let pause_msg = ExecuteMsg::SetMode { mode: Mode::Paused };
+        let owner_info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), owner_info, pause_msg).unwrap();
+        let msg = QueryMsg::Quote {
+            amount_in,
+            sender: None,
+        };
+        let _err = query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    }
+
+    #[test]
+    fn equivalent_src_paths() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let amount = 1_000_000_000_000_000;
+        let via_other_channel = mock_info("anyone", &coins(amount, "ibc/other"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &via_other_channel,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        );
+        match res {
+            Err(ContractError::InvalidFunds {}) => {}
+            _ => panic!("Must return invalid funds error"),
+        }
+
+        let msg = ExecuteMsg::AddSrcPath {
+            denom: "ibc/other".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::SrcPathAlreadyRegistered { .. }) => {}
+            _ => panic!("Must return source path already registered error"),
+        }
+
+        // both paths convert at the same rate, and are accounted for separately
+        let res = convert_tokens(
+            deps.as_mut(),
+            &via_other_channel,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+        let data: ConvertResultResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::new(1_000), data.amount_out);
+        let info = mock_info("anyone", &coins(2 * amount, "erc20token"));
+        let _res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::SrcPaths {}).unwrap();
+        let value: SrcPathsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![
+                SrcPath {
+                    denom: "erc20token".to_string(),
+                    converted: Uint128::new(2 * amount),
+                },
+                SrcPath {
+                    denom: "ibc/other".to_string(),
+                    converted: Uint128::new(amount),
+                },
+            ],
+            value.paths
+        );
+    }
+
+    #[test]
+    fn receipts_for_large_conversions() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetReceipts {
+            nft_contract: Some("receipts".to_string()),
+            threshold: Uint128::new(1_000_000),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // below the threshold, no receipt
+        let info = mock_info("anyone", &coins(999_000_000_000_000_000, "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(1, res.messages.len());
+
+        let amount = 1_000_000_000_000_000_000;
+        let info = mock_info("anyone", &coins(amount, "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+        let mint = ReceiptMintMsg {
+            token_id: "1".to_string(),
+            owner: "anyone".to_string(),
+            token_uri: None,
+            extension: ReceiptMetadata {
+                id: 1,
+                src_denom: "erc20token".to_string(),
+                src_amount: Uint128::new(amount),
+                dest_denom: "cosmostoken".to_string(),
+                dest_amount: Uint128::new(1_000_000),
+                height: mock_env().block.height,
+                memo: None,
+            },
+        };
+        assert_eq!(
+            SubMsg::new(mint.into_cosmos_msg("receipts").unwrap()),
+            res.messages[1]
+        );
+    }
+
+    #[test]
+    fn promotional_offers() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let env = mock_env();
+        let offers = vec![(100, 2_000), (300, 1_000)];
+        for (rate_bonus_bps, per_user_cap) in offers {
+            let msg = ExecuteMsg::PostOffer {
+                rate_bonus_bps,
+                per_user_cap: Uint128::new(per_user_cap),
+                total_cap: Uint128::new(10_000),
+                expiration: Expiration::AtHeight(env.block.height + 10),
+            };
+            let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+
+        // the best offer applies, and the next one takes over once the sender used it up
+        let msg = QueryMsg::Quote {
+            amount_in: Uint128::new(1_000_000_000_000_000),
+            sender: Some("anyone".to_string()),
+        };
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let quote: QuoteResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(30), quote.promo_bonus);
+        let info = mock_info("anyone", &coins(1_000_000_000_000_000, "erc20token"));
+        for expected in [1_030, 1_010].iter() {
+            let res = convert_tokens(
+                deps.as_mut(),
+                &info,
+                env.clone(),
+                None,
+                ConvertOptions::default(),
+            )
+            .unwrap();
+            let data: ConvertResultResponse = from_binary(&res.data.unwrap()).unwrap();
+            assert_eq!(Uint128::new(*expected), data.amount_out);
+        }
+
+        // expired offers no longer show up
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Offers {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: OffersResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.offers.len());
+        let mut later = env;
+        later.block.height += 10;
+        let res = query(
+            deps.as_ref(),
+            later,
+            QueryMsg::Offers {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: OffersResponse = from_binary(&res).unwrap();
+        assert!(value.offers.is_empty());
+    }
+
+    #[test]
+    fn reserve_low_events() {
+        let mut deps = mock_dependencies_with_balance(&coins(1_500, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetReserveThresholds {
+            thresholds: vec![Uint128::new(100), Uint128::new(1_000)],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // converting 1000 out of 1500 only crosses the upper threshold
+        let info = mock_info("anyone", &coins(1_000_000_000_000_000, "erc20token"));
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+        let expected = Event::new("reserve_low")
+            .add_attribute("denom", "cosmostoken")
+            .add_attribute("threshold", "1000")
+            .add_attribute("reserve", "500")
+            .add_attribute("shortfall", "500");
+        assert_eq!(vec![expected], res.events);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ReserveStatus {}).unwrap();
+        let value: ReserveStatusResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1_500), value.reserve);
+        assert!(value
+            .thresholds
+            .iter()
+            .all(|status| status.shortfall.is_zero()));
+    }
+
+    #[test]
+    fn audit_reconciles_ledger() {
+        let mut deps = mock_dependencies_with_balance(&coins(1_500, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("anyone", &coins(1_000_000_000_000_000, "erc20token"));
+        let _res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+        deps.querier.update_balance(
+            MOCK_CONTRACT_ADDR,
+            vec![
+                Coin::new(500, "cosmostoken"),
+                Coin::new(1_000_000_000_000_000, "erc20token"),
+            ],
+        );
+
+        // the reserve was sent straight to the contract, so the ledger cannot explain it
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Audit {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: AuditResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.denoms.len());
+        assert_eq!("cosmostoken", value.denoms[0].denom);
+        assert_eq!(Uint128::new(1_000), value.denoms[0].outflows);
+        assert_eq!(Uint128::new(1_500), value.denoms[0].surplus);
+        assert_eq!("erc20token", value.denoms[1].denom);
+        assert!(value.denoms[1].surplus.is_zero());
+        assert!(value.denoms[1].deficit.is_zero());
+    }
+
+    #[test]
+    fn average_rate_over_recent_conversions() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let owner = mock_info("creator", &coins(100, "cosmostoken"));
+        let _res = instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            owner.clone(),
+            ExecuteMsg::FundSubsidy {},
+        )
+        .unwrap();
+
+        // one conversion with a 10% bonus, then one without
+        // an hour apart
+        let info = mock_info("anyone", &coins(1_000_000_000_000_000, "erc20token"));
+        let mut env = mock_env();
+        for bonus_bps in [1_000, 0].iter() {
+            let msg = ExecuteMsg::SetSubsidyBonus {
+                bonus_bps: *bonus_bps,
+            };
+            let _res = execute(deps.as_mut(), env.clone(), owner.clone(), msg).unwrap();
+            let _res = convert_tokens(
+                deps.as_mut(),
+                &info,
+                env.clone(),
+                None,
+                ConvertOptions::default(),
+            )
+            .unwrap();
+            env.block.time = env.block.time.plus_seconds(3600);
+        }
+
+        let msg = QueryMsg::AverageRate {
+            last: Some(1),
+            since: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AverageRateResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.conversions);
+        assert_eq!(Decimal::one(), value.rate);
+
+        let msg = QueryMsg::AverageRate {
+            last: None,
+            since: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AverageRateResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.conversions);
+        assert_eq!(Uint128::new(2_100), value.amount_out);
+        assert_eq!(Decimal::permille(1_050), value.rate);
+
+        // a window only covers the conversions made since its start
+        let msg = QueryMsg::AverageRate {
+            last: None,
+            since: Some(mock_env().block.time.plus_seconds(1800)),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AverageRateResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.conversions);
+        assert_eq!(Uint128::new(1_000), value.amount_out);
+        assert_eq!(Decimal::one(), value.rate);
+
+        // and may not cover any
+        let msg = QueryMsg::AverageRate {
+            last: None,
+            since: Some(mock_env().block.time.plus_seconds(7200)),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AverageRateResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.conversions);
+        assert_eq!(Uint128::zero(), value.amount_out);
+    }
+
+    #[test]
+    fn simulate_batch_carries_state_over() {
+        let mut deps = mock_dependencies_with_balance(&coins(1_500, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let owner = mock_info("creator", &coins(150, "cosmostoken"));
+        let _res = instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            owner.clone(),
+            ExecuteMsg::FundSubsidy {},
+        )
+        .unwrap();
+        let msg = ExecuteMsg::SetSubsidyBonus { bonus_bps: 1_000 };
+        let _res = execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        // after the first bonus the pool cannot cover another full one, and what the first
+        // conversion took leaves the reserve short for the last one
+        let msg = QueryMsg::SimulateBatch {
+            inputs: vec![
+                Uint128::new(1_000_000_000_000_000),
+                Uint128::new(1_000_000_000_000_000),
+                Uint128::new(500_000_000_000_000),
+            ],
+            sender: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: SimulateBatchResponse = from_binary(&res).unwrap();
+        let amounts_out: Vec<_> = value.results.iter().map(|r| r.amount_out.u128()).collect();
+        assert_eq!(vec![1_100, 0, 0], amounts_out);
+        assert_eq!(
+            Some(ContractError::SubsidyExhausted {}.to_string()),
+            value.results[1].error
+        );
+        assert_eq!(
+            Some(ContractError::InsufficientFunds {}.to_string()),
+            value.results[2].error
+        );
+
+        // an empty conversion is refused like it would be when settling
+        let msg = QueryMsg::SimulateBatch {
+            inputs: vec![Uint128::zero()],
+            sender: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: SimulateBatchResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            Some(ContractError::InvalidZeroAmount {}.to_string()),
+            value.results[0].error
+        );
+    }
+
+    #[test]
+    fn partial_fill_refunds_remainder() {
+        let mut deps = mock_dependencies_with_balance(&coins(1_500, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("anyone", &coins(2_000_000_000_000_000, "erc20token"));
+        let options = ConvertOptions {
+            allow_partial: true,
+            ..ConvertOptions::default()
+        };
+        let res = convert_tokens(deps.as_mut(), &info, mock_env(), None, options).unwrap();
+        let data: ConvertResultResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::new(1_500), data.amount_out);
+        assert_eq!(Uint128::new(500_000_000_000_000), data.refunded);
+        assert_eq!(
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "anyone".to_string(),
+                    amount: coins(1_500, "cosmostoken"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "anyone".to_string(),
+                    amount: coins(500_000_000_000_000, "erc20token"),
+                }),
+            ],
+            res.messages
+        );
+
+        // an active offer's bonus is paid out of the same reserve, so the fill shrinks to match
+        let owner = mock_info("creator", &[]);
+        let msg = ExecuteMsg::PostOffer {
+            rate_bonus_bps: 2_000,
+            per_user_cap: Uint128::new(10_000),
+            total_cap: Uint128::new(10_000),
+            expiration: Expiration::Never {},
+        };
+        let _res = execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+        let options = ConvertOptions {
+            allow_partial: true,
+            ..ConvertOptions::default()
+        };
+        let res = convert_tokens(deps.as_mut(), &info, mock_env(), None, options).unwrap();
+        let data: ConvertResultResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::new(1_500), data.amount_out);
+        assert_eq!(Uint128::new(750_000_000_000_000), data.refunded);
+
+        // nothing to fill at all is still an error
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+        let options = ConvertOptions {
+            allow_partial: true,
+            ..ConvertOptions::default()
+        };
+        let res = convert_tokens(deps.as_mut(), &info, mock_env(), None, options);
+        match res {
+            Err(ContractError::InsufficientFunds {}) => {}
+            _ => panic!("Must return insufficient funds error"),
+        }
+    }
+
+    #[test]
+    fn set_metadata() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let metadata = Metadata {
+            name: "ERC20 to Cosmos".to_string(),
+            description: "One to one migration".to_string(),
+            logo_uri: Some("https://example.com/logo.png".to_string()),
+            docs_uri: None,
+        };
+        let msg = ExecuteMsg::SetMetadata {
+            metadata: metadata.clone(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Metadata {}).unwrap();
+        let value: Metadata = from_binary(&res).unwrap();
+        assert_eq!(metadata, value);
+
+        let msg = ExecuteMsg::SetMetadata {
+            metadata: Metadata {
+                description: "x".repeat(MAX_METADATA_LENGTH + 1),
+                ..metadata
+            },
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::MetadataTooLong { .. }) => {}
+            _ => panic!("Must return metadata too long error"),
+        }
+    }
+
+    #[test]
+    fn convert_message() {
+        let mut deps = mock_dependencies_with_balance(&coins(1_000, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("anyone", &coins(1_000_000_000_000_000, "erc20token"));
+        let msg = ExecuteMsg::Convert {
+            src_token_amount: Some(Uint128::new(1_000_000_000_000_000)),
+            options: Box::new(ConvertOptions::default()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "anyone".to_string(),
+                amount: coins(1_000, "cosmostoken"),
+            })],
+            res.messages
+        );
+        assert_eq!(
+            vec![
+                attr("method", "convert"),
+                attr("sender", "anyone"),
+                attr("src_denom", "erc20token"),
+                attr("amount_in", "1000000000000000"),
+                attr("amount_out", "1000"),
+            ],
+            res.attributes
+        );
+
+        // the attached funds must match the requested amount
+        let msg = ExecuteMsg::Convert {
+            src_token_amount: Some(Uint128::new(1)),
+            options: Box::new(ConvertOptions::default()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::InvalidFunds {}) => {}
+            _ => panic!("Must return invalid funds error"),
+        }
+    }
+
+    #[test]
+    fn emergency_withdraw() {
+        let mut deps =
+            mock_dependencies_with_balance(&[coin(700, "cosmostoken"), coin(5, "erc20token")]);
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::EmergencyWithdraw {
+            denom: "cosmostoken".to_string(),
+            recipient: "treasury".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // a live contract has to be paused first
+        let provider_info = mock_info("provider", &coins(200, "cosmostoken"));
+        let _res = deposit_dest_tokens(deps.as_mut(), &provider_info, mock_env()).unwrap();
+        let fund_info = mock_info("creator", &coins(100, "cosmostoken"));
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            fund_info,
+            ExecuteMsg::FundSubsidy {},
+        )
+        .unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone());
+        match res {
+            Err(ContractError::NotPaused {}) => {}
+            _ => panic!("Must return not paused error"),
+        }
+        let mode_msg = ExecuteMsg::SetMode { mode: Mode::Paused };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), mode_msg).unwrap();
+
+        // only the requested denom is swept, and the books it backed are cleared
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(700, "cosmostoken"),
+            })],
+            res.messages
+        );
+        let event = &res.events[0];
+        assert!(event.attributes.contains(&attr("deposits_cleared", "200")));
+        assert!(event.attributes.contains(&attr("subsidy_cleared", "100")));
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Deposit {
+                address: "provider".to_string(),
+            },
+        )
+        .unwrap();
+        let value: DepositResponse = from_binary(&res).unwrap();
+        assert!(value.amount.is_zero());
+        let subsidy = SUBSIDY.load(deps.as_ref().storage).unwrap();
+        assert!(subsidy.pool.is_zero());
+
+        // later deposits start from a clean book
+        let info = mock_info("provider", &coins(50, "cosmostoken"));
+        let res = deposit_dest_tokens(deps.as_mut(), &info, mock_env()).unwrap();
+        assert!(res.events[0].attributes.contains(&attr("deposited", "50")));
+        let msg = ExecuteMsg::WithdrawLiquidity {
+            amount: Uint128::new(51),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("provider", &[]), msg);
+        match res {
+            Err(ContractError::WithdrawalExceedsDeposit { deposited }) => {
+                assert_eq!(Uint128::new(50), deposited)
+            }
+            _ => panic!("Must return withdrawal exceeds deposit error"),
+        }
+    }
+
+    #[test]
+    fn convert_back_pays_from_escrow() {
+        let mut deps =
+            mock_dependencies_with_balance(&coins(1_500_000_000_000_000_000, "erc20token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let max_back = QueryMsg::MaxConvertible {
+            direction: Direction::DestToSrc,
+            address: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), max_back.clone()).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1_500_000), value.amount);
+
+        let info = mock_info("anyone", &coins(4_000_000_000_000_000_000, "erc20token"));
+        let msg = ExecuteMsg::Convert {
+            src_token_amount: None,
+            options: Box::new(ConvertOptions::default()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // converting back right after converting is refused
+        let info = mock_info("anyone", &coins(1_000_000, "cosmostoken"));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::ConvertBack {},
+        );
+        match res {
+            Err(ContractError::RoundTripCooldown { until }) => {
+                assert_eq!(
+                    mock_env().block.time.plus_seconds(ROUND_TRIP_COOLDOWN),
+                    until
+                )
+            }
+            _ => panic!("Must return round trip cooldown error"),
+        }
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(ROUND_TRIP_COOLDOWN);
+
+        // an address sees its own limits: nothing during the cooldown, and nothing beyond what
+        // it converted
+        let max_back_of = |address: &str| QueryMsg::MaxConvertible {
+            direction: Direction::DestToSrc,
+            address: Some(address.to_string()),
+        };
+        let res = query(deps.as_ref(), mock_env(), max_back_of("anyone")).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::zero(), value.amount);
+        let res = query(deps.as_ref(), env.clone(), max_back_of("anyone")).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1_500_000), value.amount);
+        let res = query(deps.as_ref(), env.clone(), max_back_of("other")).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::zero(), value.amount);
+
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::ConvertBack {}).unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "anyone".to_string(),
+                amount: coins(1_000_000_000_000_000_000, "erc20token"),
+            })],
+            res.messages
+        );
+
+        // the escrow has to cover the whole output
+        let info = mock_info("anyone", &coins(2_000_000, "cosmostoken"));
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::ConvertBack {});
+        match res {
+            Err(ContractError::InsufficientFunds {}) => {}
+            _ => panic!("Must return insufficient funds error"),
+        }
+
+        // holders can still exit once the contract is deprecated
+        let owner_info = mock_info("creator", &[]);
+        let mode_msg = ExecuteMsg::SetMode {
+            mode: Mode::Deprecated,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), owner_info, mode_msg).unwrap();
+        let res = query(deps.as_ref(), env.clone(), max_back).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1_500_000), value.amount);
+        let max_convert = QueryMsg::MaxConvertible {
+            direction: Direction::SrcToDest,
+            address: None,
+        };
+        let res = query(deps.as_ref(), env.clone(), max_convert).unwrap();
+        let value: MaxConvertibleResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::zero(), value.amount);
+        let info = mock_info("anyone", &coins(500_000, "cosmostoken"));
+        let _res = execute(deps.as_mut(), env, info, ExecuteMsg::ConvertBack {}).unwrap();
+    }
+
+    #[test]
+    fn convert_back_keeps_bonuses() {
+        let mut deps = mock_dependencies_with_balance(&[
+            coin(1_000_000_000_000_000_000, "erc20token"),
+            coin(2_000_000, "cosmostoken"),
+        ]);
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &coins(100_000, "cosmostoken")),
+            ExecuteMsg::FundSubsidy {},
+        )
+        .unwrap();
+        let bonus_msg = ExecuteMsg::SetSubsidyBonus { bonus_bps: 1_000 };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), bonus_msg).unwrap();
+        let emission_msg = ExecuteMsg::SetEmission {
+            emission: Some(Emission {
+                denom: "reward".to_string(),
+                per_unit: Decimal::percent(10),
+                end: mock_env().block.time.plus_seconds(1_000),
+            }),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, emission_msg).unwrap();
+
+        // 1_000_000 out plus a 100_000 bonus
+        let info = mock_info("anyone", &coins(1_000_000_000_000_000_000, "erc20token"));
+        let msg = ExecuteMsg::Convert {
+            src_token_amount: None,
+            options: Box::new(ConvertOptions::default()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the unsubsidized output comes back as source tokens
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(ROUND_TRIP_COOLDOWN);
+        let info = mock_info("anyone", &coins(1_100_000, "cosmostoken"));
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::ConvertBack {});
+        match res {
+            Err(ContractError::ExceedsReturnable { returnable }) => {
+                assert_eq!(Uint128::new(1_000_000), returnable)
+            }
+            _ => panic!("Must return exceeds returnable error"),
+        }
+        let info = mock_info("anyone", &coins(1_000_000, "cosmostoken"));
+        let _res = execute(deps.as_mut(), env, info, ExecuteMsg::ConvertBack {}).unwrap();
+
+        // and the points and rewards earned by the conversion go back with it
+        let points = POINTS
+            .load(deps.as_ref().storage, &Addr::unchecked("anyone"))
+            .unwrap();
+        assert_eq!(Uint128::new(100_000), points);
+        let msg = QueryMsg::Rewards {
+            address: "anyone".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: RewardsResponse = from_binary(&res).unwrap();
+        assert_eq!(coins(10_000, "reward"), value.rewards);
+    }
+
+    #[test]
+    fn update_config_patches_pair() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: true,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::UpdateConfig {
+            src_denom: None,
+            dest_denom: Some("ibc/COSMOSTOKEN".to_string()),
+            src_decimals: None,
+            dest_decimals: Some(8),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
         match res {
             Err(ContractError::Unauthorized {}) => {}
             _ => panic!("Must return unauthorized error"),
         }
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        let pair = PAIR_CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!("erc20token", pair.src_ic20_denom);
+        assert_eq!("ibc/COSMOSTOKEN", pair.dest_ic20_denom);
+        assert_eq!(Uint128::new(100_000_000), pair.dest_whole_token);
+        let event = &res.events[0];
+        assert_eq!("update_config", event.ty);
+        assert!(event
+            .attributes
+            .contains(&attr("old_dest_denom", "cosmostoken")));
+        assert!(event
+            .attributes
+            .contains(&attr("dest_denom", "ibc/COSMOSTOKEN")));
 
-        // only the original creator can reset the counter
-        let auth_info = mock_info("creator", &coins(2, "token"));
-        let msg = ExecuteMsg::Reset { count: 5 };
-        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        // the pair has to stay a pair
+        let same = ExecuteMsg::UpdateConfig {
+            src_denom: Some("ibc/COSMOSTOKEN".to_string()),
+            dest_denom: None,
+            src_decimals: None,
+            dest_decimals: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), same);
+        match res {
+            Err(ContractError::SameDenom {}) => {}
+            _ => panic!("Must return same denom error"),
+        }
 
-        // should now be 5
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: CountResponse = from_binary(&res).unwrap();
-        assert_eq!(5, value.count);
+        // and the source cannot become one of its own accepted paths
+        let add_path = ExecuteMsg::AddSrcPath {
+            denom: "ibc/ERC20TOKEN".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), add_path).unwrap();
+        let to_path = ExecuteMsg::UpdateConfig {
+            src_denom: Some("ibc/ERC20TOKEN".to_string()),
+            dest_denom: None,
+            src_decimals: None,
+            dest_decimals: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), to_path);
+        match res {
+            Err(ContractError::SrcPathAlreadyRegistered { denom }) => {
+                assert_eq!("ibc/ERC20TOKEN", denom)
+            }
+            _ => panic!("Must return src path already registered error"),
+        }
+
+        // nor can the destination change while anything is owed or configured in its units
+        let thresholds = ExecuteMsg::SetReserveThresholds {
+            thresholds: vec![Uint128::new(10)],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), thresholds).unwrap();
+        let redenominate = ExecuteMsg::UpdateConfig {
+            src_denom: None,
+            dest_denom: None,
+            src_decimals: None,
+            dest_decimals: Some(6),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), redenominate);
+        match res {
+            Err(ContractError::DestInUse {}) => {}
+            _ => panic!("Must return dest in use error"),
+        }
+        let src_only = ExecuteMsg::UpdateConfig {
+            src_denom: None,
+            dest_denom: None,
+            src_decimals: Some(9),
+            dest_decimals: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), src_only).unwrap();
+
+        // not while conversions are running
+        let resume = ExecuteMsg::SetMode { mode: Mode::Active };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), resume).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::NotPaused {}) => {}
+            _ => panic!("Must return not paused error"),
+        }
+    }
+
+    #[test]
+    fn close_settles_and_stops_everything() {
+        let balances = vec![
+            coin(500, "cosmostoken"),
+            coin(100_000_000_000_000, "erc20token"),
+            coin(10, "reward"),
+        ];
+        let mut deps = mock_dependencies_with_balance(&balances);
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only a paused contract can be closed, and only through Close
+        let owner_info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Close {
+            recipient: "treasury".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone());
+        match res {
+            Err(ContractError::NotPaused {}) => {}
+            _ => panic!("Must return not paused error"),
+        }
+        let mode_msg = ExecuteMsg::SetMode { mode: Mode::Closed };
+        let res = execute(deps.as_mut(), mock_env(), owner_info.clone(), mode_msg);
+        match res {
+            Err(ContractError::CloseRequired {}) => {}
+            _ => panic!("Must return close required error"),
+        }
+
+        // a deposit, a conversion that can be returned and a funded emission
+        let provider_info = mock_info("provider", &coins(100, "cosmostoken"));
+        let deposit_msg = ExecuteMsg::DepositLiquidity {};
+        let _res = execute(deps.as_mut(), mock_env(), provider_info, deposit_msg).unwrap();
+        let convert_msg = ExecuteMsg::Convert {
+            src_token_amount: None,
+            options: Box::new(ConvertOptions::default()),
+        };
+        let anyone_info = mock_info("anyone", &coins(100_000_000_000_000, "erc20token"));
+        let _res = execute(deps.as_mut(), mock_env(), anyone_info, convert_msg).unwrap();
+        let emission_msg = ExecuteMsg::SetEmission {
+            emission: Some(Emission {
+                denom: "reward".to_string(),
+                per_unit: Decimal::percent(10),
+                end: mock_env().block.time.plus_seconds(1_000),
+            }),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), owner_info.clone(), emission_msg).unwrap();
+        let fund_msg = ExecuteMsg::FundRewards {};
+        let fund_info = mock_info("creator", &coins(10, "reward"));
+        let _res = execute(deps.as_mut(), mock_env(), fund_info, fund_msg).unwrap();
+        let pause_msg = ExecuteMsg::SetMode { mode: Mode::Paused };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            owner_info.clone(),
+            pause_msg.clone(),
+        )
+        .unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // depositors are paid back before anything is swept
+        let res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone());
+        match res {
+            Err(ContractError::DepositsOutstanding {}) => {}
+            _ => panic!("Must return deposits outstanding error"),
+        }
+        let withdraw_msg = ExecuteMsg::WithdrawLiquidity {
+            amount: Uint128::new(100),
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("provider", &[]),
+            withdraw_msg,
+        )
+        .unwrap();
+
+        // and so are converted tokens, while deprecated
+        let res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone());
+        match res {
+            Err(ContractError::EscrowOutstanding {}) => {}
+            _ => panic!("Must return escrow outstanding error"),
+        }
+        let mode_msg = ExecuteMsg::SetMode {
+            mode: Mode::Deprecated,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), owner_info.clone(), mode_msg).unwrap();
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(ROUND_TRIP_COOLDOWN);
+        let back_info = mock_info("anyone", &coins(100, "cosmostoken"));
+        let _res = execute(deps.as_mut(), env, back_info, ExecuteMsg::ConvertBack {}).unwrap();
+        let _res = execute(deps.as_mut(), mock_env(), owner_info.clone(), pause_msg).unwrap();
+
+        // funded rewards have to be claimed or deliberately swept
+        let res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone());
+        match res {
+            Err(ContractError::RewardsOutstanding {}) => {}
+            _ => panic!("Must return rewards outstanding error"),
+        }
+        let sweep_msg = ExecuteMsg::EmergencyWithdraw {
+            denom: "reward".to_string(),
+            recipient: "treasury".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner_info.clone(), sweep_msg).unwrap();
+        assert!(res.events[0]
+            .attributes
+            .contains(&attr("rewards_cleared", "10")));
+
+        let res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: balances,
+            })],
+            res.messages
+        );
+
+        // closing is terminal
+        let res = execute(deps.as_mut(), mock_env(), owner_info, msg);
+        match res {
+            Err(ContractError::Closed {}) => {}
+            _ => panic!("Must return closed error"),
+        }
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Mode {}).unwrap();
+        let value: ModeResponse = from_binary(&res).unwrap();
+        assert_eq!(Mode::Closed, value.mode);
+    }
+
+    #[test]
+    fn config_round_trip() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        let msgs = vec![
+            ExecuteMsg::AddHook {
+                addr: "rewards".to_string(),
+            },
+            ExecuteMsg::AddVault {
+                addr: "vault".to_string(),
+            },
+            ExecuteMsg::GrantRole {
+                addr: "manager".to_string(),
+                role: Role::HookManager,
+            },
+            ExecuteMsg::SetSubsidyBonus { bonus_bps: 250 },
+        ];
+        for msg in msgs {
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ExportConfig {}).unwrap();
+        let config: ExportedConfig = from_binary(&res).unwrap();
+        assert_eq!(vec!["rewards".to_string()], config.hooks);
+        assert_eq!(250, config.subsidy_bonus_bps);
+
+        // a fresh deployment ends up configured the same way
+        let mut fresh = mock_dependencies_with_balance(&[]);
+        let _res = instantiate(fresh.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::ImportConfig {
+            config: Box::new(config.clone()),
+        };
+        let res = execute(
+            fresh.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(fresh.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        let res = query(fresh.as_ref(), mock_env(), QueryMsg::ExportConfig {}).unwrap();
+        let imported: ExportedConfig = from_binary(&res).unwrap();
+        assert_eq!(config, imported);
+
+        // an invalid configuration is rejected as a whole, before anything is saved
+        let invalid = vec![
+            ExportedConfig {
+                dest_ic20_denom: "othertoken".to_string(),
+                reserve_thresholds: vec![Uint128::new(1); MAX_RESERVE_THRESHOLDS + 1],
+                ..config.clone()
+            },
+            ExportedConfig {
+                src_paths: vec!["cosmostoken".to_string()],
+                ..config.clone()
+            },
+            ExportedConfig {
+                src_paths: vec!["erc20token".to_string()],
+                ..config.clone()
+            },
+            ExportedConfig {
+                dest_ic20_denom: "erc20token".to_string(),
+                ..config.clone()
+            },
+            ExportedConfig {
+                emission: Some(Emission {
+                    denom: "cosmostoken".to_string(),
+                    per_unit: Decimal::percent(1),
+                    end: mock_env().block.time,
+                }),
+                ..config.clone()
+            },
+        ];
+        for config in invalid {
+            let msg = ExecuteMsg::ImportConfig {
+                config: Box::new(config),
+            };
+            let _err = execute(fresh.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        }
+        let res = query(fresh.as_ref(), mock_env(), QueryMsg::ExportConfig {}).unwrap();
+        let imported: ExportedConfig = from_binary(&res).unwrap();
+        assert_eq!(config, imported);
+
+        // repeated source paths are only accepted once
+        let repeated = ExecuteMsg::ImportConfig {
+            config: Box::new(ExportedConfig {
+                src_paths: vec![
+                    "ibc/a".to_string(),
+                    "ibc/b".to_string(),
+                    "ibc/a".to_string(),
+                ],
+                ..config.clone()
+            }),
+        };
+        let _res = execute(fresh.as_mut(), mock_env(), info.clone(), repeated).unwrap();
+        let res = query(fresh.as_ref(), mock_env(), QueryMsg::ExportConfig {}).unwrap();
+        let imported: ExportedConfig = from_binary(&res).unwrap();
+        assert_eq!(
+            vec!["ibc/a".to_string(), "ibc/b".to_string()],
+            imported.src_paths
+        );
+
+        // but not once it has been used
+        let _res = deposit_dest_tokens(
+            fresh.as_mut(),
+            &mock_info("provider", &coins(100, "cosmostoken")),
+            mock_env(),
+        )
+        .unwrap();
+        let res = execute(fresh.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::AlreadyInUse {}) => {}
+            _ => panic!("Must return already in use error"),
+        }
+    }
+
+    #[test]
+    fn deposit_emits_event() {
+        let mut deps = mock_dependencies_with_balance(&coins(500, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("provider", &coins(200, "cosmostoken"));
+        let res = deposit_dest_tokens(deps.as_mut(), &info, mock_env()).unwrap();
+        assert_eq!(1, res.events.len());
+        let event = &res.events[0];
+        assert_eq!("deposit", event.ty);
+        assert_eq!("provider", event.attributes[0].value);
+        assert_eq!("cosmostoken", event.attributes[1].value);
+        assert_eq!("200", event.attributes[2].value);
+        assert_eq!("500", event.attributes[3].value);
+
+        // deposits add up per depositor
+        let msg = ExecuteMsg::DepositLiquidity {};
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!("400", res.events[0].attributes[4].value);
+        let msg = QueryMsg::Deposit {
+            address: "provider".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: DepositResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(400), value.amount);
+
+        // an empty deposit is refused
+        let res = deposit_dest_tokens(deps.as_mut(), &mock_info("provider", &[]), mock_env());
+        match res {
+            Err(ContractError::InvalidZeroAmount {}) => {}
+            _ => panic!("Must return invalid zero amount error"),
+        }
+
+        // only the destination token can be deposited
+        let info = mock_info("provider", &coins(200, "erc20token"));
+        let res = deposit_dest_tokens(deps.as_mut(), &info, mock_env());
+        match res {
+            Err(ContractError::InvalidFunds {}) => {}
+            _ => panic!("Must return invalid funds error"),
+        }
+    }
+
+    #[test]
+    fn withdraw_liquidity() {
+        let mut deps = mock_dependencies_with_balance(&coins(500, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetReserveThresholds {
+            thresholds: vec![Uint128::new(300)],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("provider", &coins(400, "cosmostoken"));
+        let _res = deposit_dest_tokens(deps.as_mut(), &info, mock_env()).unwrap();
+
+        // depositors can take back what they put in
+        let info = mock_info("provider", &[]);
+        let msg = ExecuteMsg::WithdrawLiquidity {
+            amount: Uint128::new(300),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "provider".to_string(),
+                amount: coins(300, "cosmostoken"),
+            })],
+            res.messages
+        );
+        let event = &res.events[0];
+        assert_eq!("withdrawal", event.ty);
+        assert_eq!("300", event.attributes[2].value);
+        assert_eq!("200", event.attributes[3].value);
+        assert_eq!("100", event.attributes[4].value);
+
+        // a withdrawal that drains the reserve past a threshold is flagged like a conversion
+        assert_eq!(2, res.events.len());
+        assert_eq!("reserve_low", res.events[1].ty);
+        assert!(res.events[1].attributes.contains(&attr("threshold", "300")));
+
+        // and no more
+        let msg = ExecuteMsg::WithdrawLiquidity {
+            amount: Uint128::new(101),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        match res {
+            Err(ContractError::WithdrawalExceedsDeposit { deposited }) => {
+                assert_eq!(Uint128::new(100), deposited)
+            }
+            _ => panic!("Must return withdrawal exceeds deposit error"),
+        }
+
+        // and nothing at all does not make a withdrawal
+        let msg = ExecuteMsg::WithdrawLiquidity {
+            amount: Uint128::zero(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::InvalidZeroAmount {}) => {}
+            _ => panic!("Must return invalid zero amount error"),
+        }
+    }
+
+    #[test]
+    fn recent_activity() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("lp", &coins(2, "cosmostoken"));
+        for _ in 0..ACTIVITY_CAPACITY + 5 {
+            deposit_dest_tokens(deps.as_mut(), &info, mock_env()).unwrap();
+        }
+        let info = mock_info("anyone", &coins(1_000_000_000_000_000, "erc20token"));
+        convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions::default(),
+        )
+        .unwrap();
+
+        let memo = "invoice 42".to_string();
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions {
+                memo: Some(memo.clone()),
+                ..ConvertOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(res.attributes.contains(&attr("memo", memo.clone())));
+
+        // memos are capped
+        let res = convert_tokens(
+            deps.as_mut(),
+            &info,
+            mock_env(),
+            None,
+            ConvertOptions {
+                memo: Some("x".repeat(MAX_MEMO_LENGTH + 1)),
+                ..ConvertOptions::default()
+            },
+        );
+        match res {
+            Err(ContractError::MemoTooLong { .. }) => {}
+            _ => panic!("Must return memo too long error"),
+        }
+
+        // newest first
+        let msg = QueryMsg::RecentActivity {
+            start_after: None,
+            limit: Some(3),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: RecentActivityResponse = from_binary(&res).unwrap();
+        assert_eq!(3, value.entries.len());
+        assert_eq!(ActivityKind::Conversion, value.entries[0].kind);
+        assert_eq!(Some(memo), value.entries[0].memo);
+        assert_eq!(ActivityKind::Conversion, value.entries[1].kind);
+        assert_eq!(None, value.entries[1].memo);
+        assert_eq!(ActivityKind::Deposit, value.entries[2].kind);
+        assert_eq!(Uint128::new(2), value.entries[2].amount_in);
+
+        // never more than a page holds, but the cursor reaches everything still stored
+        let mut start_after = None;
+        let mut seen = 0;
+        loop {
+            let msg = QueryMsg::RecentActivity {
+                start_after,
+                limit: Some(1000),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: RecentActivityResponse = from_binary(&res).unwrap();
+            assert!(value.entries.len() <= MAX_LIMIT as usize);
+            if value.entries.is_empty() {
+                break;
+            }
+            seen += value.entries.len();
+            start_after = value.last_seq;
+        }
+        assert_eq!(ACTIVITY_CAPACITY as usize, seen);
+    }
+
+    #[test]
+    fn conversion_overflow() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "cosmostoken"));
+
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 0,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 38,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // ten whole source tokens are worth more destination units than fit in 128 bits
+        let info = mock_info("anyone", &coins(10, "erc20token"));
+        let msg = ExecuteMsg::Convert {
+            src_token_amount: Some(Uint128::new(10)),
+            options: Box::new(ConvertOptions::default()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::ConversionOverflow {}) => {}
+            _ => panic!("Must return conversion overflow error"),
+        }
     }
+
+    /// Convert between tokens with different decimals.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - the amount of the input token to convert
+    /// * `rate` - corresponds to the output token decimals. E.g: If we want 1:1 rate and the output token has 6 decimals, then rate = 1_000_000
+    /// * `input_decimals` - the number of decimals of the input token
+    /// * `output_decimals` - the number of decimals of the output token
+    fn calculate_token_conversion_output(
+        amount: u128,
+        rate: u128,
+        input_decimals: u8,
+        output_decimals: u8,
+    ) -> StdResult<ConvertTokenResponse> {
+        // result = amount * rate / one whole output token
+        let mut result = amount * rate;
+
+        // But, if tokens have different number of decimals, we need to compensate either by
+        // dividing or multiplying (depending on which token has more decimals) the difference
+        if input_decimals < output_decimals {
+            let compensation = get_whole_token_representation(output_decimals - input_decimals);
+            result *= compensation
+        } else if output_decimals < input_decimals {
+            let compensation = get_whole_token_representation(input_decimals - output_decimals);
+            result /= compensation
+        }
+
+        let whole_token = get_whole_token_representation(output_decimals);
+
+        let result = result / whole_token;
+
+        Ok(ConvertTokenResponse { amount: result })
+    }
+
     #[test]
     fn test_convert_token() {
         // Assuming the user friendly (in the UI) exchange rate has been set to
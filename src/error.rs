@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Timestamp, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -16,6 +16,144 @@ pub enum ContractError {
     #[error("InsufficientFunds")]
     InsufficientFunds {},
 
+    #[error("Cannot withdraw more than the {deposited} deposited")]
+    WithdrawalExceedsDeposit { deposited: Uint128 },
+
     #[error("Invalid funds")]
     InvalidFunds {},
+
+    #[error("Address not allowed by the compliance registry: {address}")]
+    AddressNotAllowed { address: String },
+
+    #[error("Split weights must add up to 10000 bps")]
+    InvalidSplits {},
+
+    #[error("Output can only go to one of splits, a vault or an attached callback")]
+    ConflictingPayout {},
+
+    #[error("Emission cannot pay out {denom}, the contract converts it")]
+    InvalidEmissionDenom { denom: String },
+
+    #[error("Source and destination denoms must differ")]
+    SameDenom {},
+
+    #[error("Balances and settings denominated in the destination token must be cleared first")]
+    DestInUse {},
+
+    #[error("Source denom already accepted: {denom}")]
+    SrcPathAlreadyRegistered { denom: String },
+
+    #[error("Source denom not registered: {denom}")]
+    SrcPathNotRegistered { denom: String },
+
+    #[error("Vault already registered: {addr}")]
+    VaultAlreadyRegistered { addr: String },
+
+    #[error("Vault not registered: {addr}")]
+    VaultNotRegistered { addr: String },
+
+    #[error("Only a contract that has not been used yet can import a configuration")]
+    AlreadyInUse {},
+
+    #[error("Contract must be paused")]
+    NotPaused {},
+
+    #[error("Cannot import more than {max} records at once")]
+    ImportBatchTooLarge { max: usize },
+
+    #[error("Contract is paused")]
+    Paused {},
+
+    #[error("Contract is closed")]
+    Closed {},
+
+    #[error("Liquidity providers must withdraw their deposits before the contract is closed")]
+    DepositsOutstanding {},
+
+    #[error("Converted tokens must be converted back before the contract is closed")]
+    EscrowOutstanding {},
+
+    #[error("Funded emission rewards must be claimed before the contract is closed")]
+    RewardsOutstanding {},
+
+    #[error("Closing moves the funds out, use Close instead")]
+    CloseRequired {},
+
+    #[error("Contract is deprecated, new conversions are disabled")]
+    Deprecated {},
+
+    #[error("Tokens cannot have more than {max} decimals")]
+    InvalidDecimals { max: u8 },
+
+    #[error("Basis points cannot exceed 10000")]
+    InvalidBps {},
+
+    #[error("Cannot convert back until {until} after converting")]
+    RoundTripCooldown { until: Timestamp },
+
+    #[error("Only {returnable} can be converted back, bonuses are not returnable")]
+    ExceedsReturnable { returnable: Uint128 },
+
+    #[error("Amount must be greater than zero")]
+    InvalidZeroAmount {},
+
+    #[error("Conversion output does not fit in 128 bits")]
+    ConversionOverflow {},
+
+    #[error("{addr} cannot be the target of a callback")]
+    InvalidCallbackTarget { addr: String },
+
+    #[error("Cannot register more than {max} hooks")]
+    TooManyHooks { max: usize },
+
+    #[error("Cannot register more than {max} vaults")]
+    TooManyVaults { max: usize },
+
+    #[error("Cannot accept more than {max} source paths")]
+    TooManySrcPaths { max: usize },
+
+    #[error("Cannot grant roles to more than {max} addresses")]
+    TooManyRoleHolders { max: usize },
+
+    #[error("Cannot have more than {max} reserve thresholds")]
+    TooManyThresholds { max: usize },
+
+    #[error("Cannot have more than {max} live offers")]
+    TooManyOffers { max: usize },
+
+    #[error("Offer not found: {id}")]
+    OfferNotFound { id: u64 },
+
+    #[error("Subsidy pool exhausted")]
+    SubsidyExhausted {},
+
+    #[error("Memo longer than {max} characters")]
+    MemoTooLong { max: usize },
+
+    #[error("Metadata fields cannot be longer than {max} characters")]
+    MetadataTooLong { max: usize },
+
+    #[error("Hook already registered: {addr}")]
+    HookAlreadyRegistered { addr: String },
+
+    #[error("Hook not registered: {addr}")]
+    HookNotRegistered { addr: String },
+
+    #[error("Role already granted")]
+    RoleAlreadyGranted {},
+
+    #[error("Role not granted")]
+    RoleNotGranted {},
+
+    #[error("Not a contract: {addr}")]
+    NotAContract { addr: String },
+
+    #[error("No ownership transfer pending")]
+    NoPendingOwnership {},
+
+    #[error("Ownership can only be accepted after {accept_after}")]
+    OwnershipTransferLocked { accept_after: Timestamp },
+
+    #[error("Unknown reply id: {id}")]
+    UnknownReplyId { id: u64 },
 }
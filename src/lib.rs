@@ -1,6 +1,11 @@
 pub mod contract;
 mod error;
+#[cfg(feature = "library")]
+pub mod library;
 pub mod msg;
+pub mod pagination;
 pub mod state;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub use crate::error::ContractError;
@@ -0,0 +1,120 @@
+//! Entry points for contracts that embed the conversion logic in their own execute paths
+//! instead of calling a deployed converter. Depend on this crate with the `library` feature so
+//! its own entry points are left out.
+//!
+//! The embedded converter keeps its state under the same storage keys as the standalone
+//! contract, so the embedding contract must not use them for anything else. Likewise, hook
+//! notifications go out as submessages with reply id `HOOK_REPLY_ID`: the embedding contract
+//! must not use that id itself, and its `reply` entry point must pass those replies on to
+//! `reply` here.
+
+use cosmwasm_std::{Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult, Storage, Uint128};
+
+use crate::contract::{convert_tokens, get_pair_config, query_quote};
+use crate::msg::{ConvertOptions, QuoteResponse};
+use crate::state::PAIR_CONFIG;
+use crate::ContractError;
+
+/// The token pair an embedded converter works with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConverterConfig {
+    pub src_denom: String,
+    pub src_decimals: u8,
+    pub dest_denom: String,
+    pub dest_decimals: u8,
+}
+
+impl ConverterConfig {
+    /// Saves the pair, typically from the embedding contract's `instantiate`.
+    pub fn save(self, storage: &mut dyn Storage) -> Result<(), ContractError> {
+        let pair = get_pair_config(
+            self.src_denom,
+            self.src_decimals,
+            self.dest_denom,
+            self.dest_decimals,
+        )?;
+        PAIR_CONFIG.save(storage, &pair)?;
+        Ok(())
+    }
+}
+
+/// Prices a conversion of `amount_in` source tokens exactly as `execute_convert` would settle
+/// it now.
+pub fn quote(
+    deps: Deps,
+    env: Env,
+    amount_in: Uint128,
+    sender: Option<String>,
+) -> StdResult<QuoteResponse> {
+    query_quote(deps, env, amount_in, sender)
+}
+
+/// Converts the source tokens attached to `info`, with the same checks, bookkeeping and
+/// payout as a conversion on the standalone contract.
+pub fn execute_convert(
+    deps: DepsMut,
+    env: Env,
+    info: &MessageInfo,
+    options: ConvertOptions,
+) -> Result<Response, ContractError> {
+    convert_tokens(deps, info, env, None, options)
+}
+
+/// Reply id of the hook notifications `execute_convert` dispatches.
+pub const HOOK_REPLY_ID: u64 = crate::contract::HOOK_REPLY_ID;
+
+/// Handles the reply to a hook notification that failed, recording the error instead of
+/// reverting the conversion. Call it from the embedding contract's `reply` entry point for
+/// replies with id `HOOK_REPLY_ID`.
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    crate::contract::reply(deps, env, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::ConvertResultResponse;
+    use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
+    use cosmwasm_std::{coins, from_binary, SubMsgResult};
+
+    #[test]
+    fn embedded_conversion_matches_quote() {
+        let mut deps = mock_dependencies_with_balance(&coins(1_000, "cosmostoken"));
+        let config = ConverterConfig {
+            src_denom: "erc20token".to_string(),
+            src_decimals: 18,
+            dest_denom: "cosmostoken".to_string(),
+            dest_decimals: 6,
+        };
+        let same = ConverterConfig {
+            dest_denom: "erc20token".to_string(),
+            ..config.clone()
+        };
+        match same.save(deps.as_mut().storage) {
+            Err(ContractError::SameDenom {}) => {}
+            _ => panic!("Must return same denom error"),
+        }
+        config.save(deps.as_mut().storage).unwrap();
+
+        let amount_in = Uint128::new(1_000_000_000_000_000);
+        let quoted = quote(deps.as_ref(), mock_env(), amount_in, None).unwrap();
+        let info = mock_info("anyone", &coins(amount_in.u128(), "erc20token"));
+        let res =
+            execute_convert(deps.as_mut(), mock_env(), &info, ConvertOptions::default()).unwrap();
+        let data: ConvertResultResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::new(1_000), quoted.amount_out);
+        assert_eq!(quoted.amount_out, data.amount_out);
+    }
+
+    #[test]
+    fn embedded_hook_failure_is_only_logged() {
+        let mut deps = mock_dependencies_with_balance(&[]);
+        let reply_msg = Reply {
+            id: HOOK_REPLY_ID,
+            result: SubMsgResult::Err("hook exploded".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        assert_eq!("hook exploded", res.attributes[1].value);
+    }
+}
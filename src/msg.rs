@@ -1,7 +1,15 @@
-use cosmwasm_std::Coin;
+use cosmwasm_std::{
+    to_binary, Binary, Coin, CosmosMsg, Decimal, StdResult, Timestamp, Uint128, WasmMsg,
+};
+use cw20::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::state::{
+    ActivityEntry, Emission, Metadata, Mode, Offer, PairConfig, PendingOwnership, ReceiptConfig,
+    Role,
+};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub count: i32,
@@ -9,13 +17,179 @@ pub struct InstantiateMsg {
     pub dest_ic20_decimals: u8,
     pub src_ic20_denom: String,
     pub src_ic20_decimals: u8,
+    /// Seconds a proposed owner has to wait before accepting, none when unset
+    #[serde(default)]
+    pub ownership_transfer_delay: u64,
+    /// Deploys in `Mode::Paused`, so the setup can be checked before anyone converts
+    #[serde(default)]
+    pub start_paused: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     Increment {},
-    Reset { count: i32 },
+    Reset {
+        count: i32,
+    },
+    /// Converts the attached source tokens into destination tokens. When `src_token_amount`
+    /// is given, the attached funds must match it exactly.
+    Convert {
+        src_token_amount: Option<Uint128>,
+        #[serde(default)]
+        options: Box<ConvertOptions>,
+    },
+    /// Converts the attached destination tokens back into the source token, paid out of the
+    /// source tokens escrowed by earlier conversions. Only what the sender converted, bonuses
+    /// excluded, can come back, and not right after converting. The points and current emission
+    /// rewards the returned tokens earned are taken back. Stays open while deprecated.
+    ConvertBack {},
+    /// Adds the attached destination tokens to the conversion reserve, crediting them to the
+    /// sender.
+    DepositLiquidity {},
+    /// Sends back up to what the sender has deposited with `DepositLiquidity`.
+    WithdrawLiquidity {
+        amount: Uint128,
+    },
+    /// Accepts `denom` as another path of the source asset, converting at the same rate.
+    /// Owner only.
+    AddSrcPath {
+        denom: String,
+    },
+    /// Stops accepting a source denom added with `AddSrcPath`. Owner only.
+    RemoveSrcPath {
+        denom: String,
+    },
+    /// Registers a contract to be notified after every conversion. Requires `Role::HookManager`.
+    AddHook {
+        addr: String,
+    },
+    /// Stops notifying a previously registered contract. Requires `Role::HookManager`.
+    RemoveHook {
+        addr: String,
+    },
+    /// Whitelists a vault converted tokens can be deposited into. Requires `Role::Operator`.
+    AddVault {
+        addr: String,
+    },
+    /// Removes a vault from the whitelist. Requires `Role::Operator`.
+    RemoveVault {
+        addr: String,
+    },
+    /// Replaces the display metadata shown by explorers. Requires `Role::Operator`.
+    SetMetadata {
+        metadata: Metadata,
+    },
+    /// Adds the attached destination tokens to the subsidy pool. Requires
+    /// `Role::IncentiveManager`.
+    FundSubsidy {},
+    /// Sets the bonus paid on top of every conversion out of the subsidy pool. Requires
+    /// `Role::IncentiveManager`.
+    SetSubsidyBonus {
+        bonus_bps: u16,
+    },
+    /// Posts a promotional offer. Conversions automatically get the best live offer with room
+    /// left under its caps, which are counted in destination tokens. Requires
+    /// `Role::IncentiveManager`.
+    PostOffer {
+        rate_bonus_bps: u16,
+        per_user_cap: Uint128,
+        total_cap: Uint128,
+        expiration: Expiration,
+    },
+    /// Ends an offer early. Requires `Role::IncentiveManager`.
+    WithdrawOffer {
+        id: u64,
+    },
+    /// Starts, replaces or (with `None`) stops the conversion reward emission. Requires
+    /// `Role::IncentiveManager`.
+    SetEmission {
+        emission: Option<Emission>,
+    },
+    /// Adds the attached reward tokens of the running emission to the pool claims are paid
+    /// from. Requires `Role::IncentiveManager`.
+    FundRewards {},
+    /// Sends the caller the emission rewards it has accrued so far, as far as the reward pool
+    /// covers them. The rest stays claimable.
+    ClaimRewards {},
+    /// Gives `addr` the privileges of `role`. Owner only.
+    GrantRole {
+        addr: String,
+        role: Role,
+    },
+    /// Takes `role` away from `addr`. Owner only.
+    RevokeRole {
+        addr: String,
+        role: Role,
+    },
+    /// Starts handing the contract over to `new_owner`, who can accept once the
+    /// ownership transfer delay has passed. Owner only.
+    ProposeOwner {
+        new_owner: String,
+    },
+    /// Same as `ProposeOwner`, but fails unless `dao` is a contract. Owner only.
+    ProposeDaoOwner {
+        dao: String,
+    },
+    /// Completes a pending ownership transfer. Proposed owner only.
+    AcceptOwnership {},
+    /// Drops a pending ownership transfer. Owner only.
+    CancelOwnershipTransfer {},
+    /// Sets or clears the registry every converting address is checked against. Owner only.
+    SetComplianceRegistry {
+        registry: Option<String>,
+    },
+    /// Pauses conversions in an emergency. Requires `Role::Guardian`, only the owner can
+    /// resume with `SetMode`.
+    Pause {},
+    /// Mints a receipt on `nft_contract` for every conversion paying out at least `threshold`,
+    /// or stops minting when `nft_contract` is left out. Requires `Role::Operator`.
+    SetReceipts {
+        nft_contract: Option<String>,
+        threshold: Uint128,
+    },
+    /// Sets the reserve levels that emit a `reserve_low` event when a conversion takes the
+    /// destination reserve below them. Requires `Role::Operator`.
+    SetReserveThresholds {
+        thresholds: Vec<Uint128>,
+    },
+    /// Corrects the pair, changing only the fields that are set. Requires `Mode::Paused`, so
+    /// nobody converts while the rate moves. The destination denom and decimals can only change
+    /// once no deposits, subsidy pool, offers, convertible-back balances or reserve thresholds
+    /// remain. Owner only.
+    UpdateConfig {
+        src_denom: Option<String>,
+        dest_denom: Option<String>,
+        src_decimals: Option<u8>,
+        dest_decimals: Option<u8>,
+    },
+    /// Switches the contract between normal operation, pause and wind-down. Owner only.
+    SetMode {
+        mode: Mode,
+    },
+    /// Applies a configuration exported from another deployment with the `ExportConfig` query.
+    /// Owner only, and only before the first conversion or deposit.
+    ImportConfig {
+        config: Box<ExportedConfig>,
+    },
+    /// Replays conversions made on a previous deployment into the history and loyalty points.
+    /// Owner only, while paused, at most 50 records per call.
+    ImportRecords {
+        records: Vec<ImportedRecord>,
+    },
+    /// Sends the contract's whole balance of `denom` to `recipient`. Sweeping the destination
+    /// token also clears the recorded deposits and the subsidy pool it backed, and sweeping a
+    /// reward denom clears its reward pool. Owner only, requires `Mode::Paused`.
+    EmergencyWithdraw {
+        denom: String,
+        recipient: String,
+    },
+    /// Sends every remaining balance to `recipient` and shuts the contract down for good.
+    /// Owner only, requires `Mode::Paused` and that no deposits, convertible-back balances or
+    /// funded rewards remain.
+    Close {
+        recipient: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -23,6 +197,122 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
     // GetCount returns the current count as a json-encoded number
     GetCount {},
+    // Hooks returns the contracts notified after every conversion
+    Hooks {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // RecentActivity returns the latest conversions, deposits and withdrawals, newest first
+    RecentActivity {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // AverageRate returns the volume-weighted rate of the last `last` conversions, up to as many
+    // as the activity log holds, optionally only those made at or after `since`
+    AverageRate {
+        last: Option<u32>,
+        since: Option<Timestamp>,
+    },
+    // Metadata returns the display metadata, empty until the owner sets it
+    Metadata {},
+    // Subsidy returns the conversion bonus and what is left in the pool paying for it
+    Subsidy {},
+    // Emission returns the current reward emission, if any
+    Emission {},
+    // Rewards returns the unclaimed emission rewards of an address
+    Rewards {
+        address: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Deposit returns the liquidity an address has deposited
+    Deposit {
+        address: String,
+    },
+    // Points returns the loyalty points an address earned by converting
+    Points {
+        address: String,
+    },
+    // Vaults returns the whitelisted vaults converted tokens can be deposited into
+    Vaults {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Roles returns the roles granted to an address
+    Roles {
+        address: String,
+    },
+    // PendingOwnership returns the ownership transfer in progress, if any
+    PendingOwnership {},
+    // Mode returns the current operating mode
+    Mode {},
+    // ExportConfig returns the whole configuration, ready to be imported with ImportConfig
+    ExportConfig {},
+    // SrcPaths returns every accepted source denom with the amount converted through it
+    SrcPaths {},
+    // Quote prices a conversion of `amount_in` source tokens exactly as it would settle now,
+    // including the offer `sender` would get. It fails where the conversion would: unless the
+    // contract is active, for a zero amount, or for a sender the compliance registry rejects
+    Quote {
+        amount_in: Uint128,
+        sender: Option<String>,
+    },
+    // SimulateBatch prices conversions of each of `inputs` as if they settled one after the
+    // other, so the subsidy pool, offers and reserve they use up carry over
+    SimulateBatch {
+        inputs: Vec<Uint128>,
+        sender: Option<String>,
+    },
+    // Offers returns the offers that can still be used
+    Offers {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Audit reconciles the internal ledger of every denom against the live balances
+    Audit {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // ReserveStatus returns the destination reserve and how far below each threshold it is
+    ReserveStatus {},
+    // MaxConvertible returns the largest input a conversion in `direction` can take right now.
+    // With an `address`, converting also pays the offers it qualifies for out of the reserve,
+    // and converting back is capped by what it may return and its cooldown. Without one,
+    // converting back only reflects the escrow
+    MaxConvertible {
+        direction: Direction,
+        address: Option<String>,
+    },
+    // ExportState returns one page of the raw entries of a storage section, for indexers.
+    // `start_after` is the last key of the previous page: a slot number for activity and an
+    // address, validated like any other, for points and deposits
+    ExportState {
+        section: StateSection,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Source tokens in, destination tokens out
+    SrcToDest,
+    /// Destination tokens in, source tokens out
+    DestToSrc,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StateSection {
+    /// Recent activity ring buffer, paged by slot
+    Activity,
+    /// Loyalty points, paged by address
+    Points,
+    /// Liquidity provider deposits, paged by address
+    Deposits,
+    /// The token pair, a single entry
+    Pair,
 }
 
 // We define a custom struct for each query response
@@ -31,7 +321,441 @@ pub struct CountResponse {
     pub count: i32,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HooksResponse {
+    pub hooks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SubsidyResponse {
+    pub bonus_bps: u16,
+    pub pool: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EmissionResponse {
+    pub emission: Option<Emission>,
+    /// Reward pool of the emission denom
+    pub pool: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardsResponse {
+    pub rewards: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DepositResponse {
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PointsResponse {
+    pub points: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VaultsResponse {
+    pub vaults: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingOwnershipResponse {
+    pub pending: Option<PendingOwnership>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportStateResponse {
+    pub entries: Vec<StateEntry>,
+}
+
+/// A storage entry as stored, along with its key. The key is also the `start_after` cursor
+/// of the next page.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StateEntry {
+    Activity { slot: u64, entry: ActivityEntry },
+    Points { address: String, points: Uint128 },
+    Deposits { address: String, amount: Uint128 },
+    Pair { pair: PairConfig },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateBatchResponse {
+    pub results: Vec<SimulatedConversion>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulatedConversion {
+    pub amount_in: Uint128,
+    pub amount_out: Uint128,
+    /// Why the conversion would fail. Failed conversions use up nothing.
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OffersResponse {
+    pub offers: Vec<OfferInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OfferInfo {
+    pub id: u64,
+    pub offer: Offer,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AverageRateResponse {
+    pub conversions: u32,
+    pub amount_in: Uint128,
+    pub amount_out: Uint128,
+    /// Destination tokens received per destination token at the nominal rate, bonuses included
+    pub rate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuditResponse {
+    pub denoms: Vec<AuditEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuditEntry {
+    pub denom: String,
+    pub inflows: Uint128,
+    pub outflows: Uint128,
+    pub balance: Uint128,
+    /// Balance the ledger does not explain, e.g. reserve top-ups sent straight to the contract
+    pub surplus: Uint128,
+    /// Balance the ledger expects but is missing
+    pub deficit: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReserveStatusResponse {
+    pub reserve: Uint128,
+    pub thresholds: Vec<ThresholdStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ThresholdStatus {
+    pub threshold: Uint128,
+    /// How much is missing to get back to the threshold, zero if the reserve is above it
+    pub shortfall: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SrcPathsResponse {
+    /// The primary source denom comes first
+    pub paths: Vec<SrcPath>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SrcPath {
+    pub denom: String,
+    pub converted: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QuoteResponse {
+    pub amount_in: Uint128,
+    /// Output at the nominal one-to-one rate
+    pub gross_amount_out: Uint128,
+    /// Paid on top out of the subsidy pool
+    pub subsidy_bonus: Uint128,
+    /// Paid on top by the best offer available to the sender
+    pub promo_bonus: Uint128,
+    /// What the sender receives
+    pub amount_out: Uint128,
+    /// Whole destination tokens received per whole source token
+    pub effective_rate: Decimal,
+    /// Largest input that can currently be converted, by the sender when one is given
+    pub max_amount_in: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MaxConvertibleResponse {
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ModeResponse {
+    pub mode: Mode,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RolesResponse {
+    pub roles: Vec<Role>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RecentActivityResponse {
+    pub entries: Vec<ActivityEntry>,
+    /// Sequence number of the oldest entry returned, the `start_after` of the next page
+    pub last_seq: Option<u64>,
+}
+
+/// Set as the `data` of every conversion response, so calling contracts can read the
+/// outcome from their submessage reply.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConvertResultResponse {
+    pub id: u64,
+    pub amount_out: Uint128,
+    /// Source tokens sent back because the reserve only covered part of the conversion
+    pub refunded: Uint128,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct ConvertTokenResponse {
     pub amount: u128,
 }
+
+/// Everything needed to set up an identical converter elsewhere. Balances, history and
+/// ownership are not part of it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportedConfig {
+    pub dest_ic20_denom: String,
+    pub dest_ic20_decimals: u8,
+    pub src_ic20_denom: String,
+    pub src_ic20_decimals: u8,
+    pub ownership_transfer_delay: u64,
+    pub hooks: Vec<String>,
+    pub vaults: Vec<String>,
+    pub compliance_registry: Option<String>,
+    pub roles: Vec<RoleGrant>,
+    pub src_paths: Vec<String>,
+    pub receipts: Option<ReceiptConfig>,
+    pub metadata: Option<Metadata>,
+    pub reserve_thresholds: Vec<Uint128>,
+    pub subsidy_bonus_bps: u16,
+    pub emission: Option<Emission>,
+}
+
+/// A conversion carried over from a previous deployment.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ImportedRecord {
+    pub address: String,
+    pub amount_in: Uint128,
+    pub amount_out: Uint128,
+    pub height: u64,
+    pub time: Timestamp,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoleGrant {
+    pub address: String,
+    pub roles: Vec<Role>,
+}
+
+/// Optional extras of a conversion. By default the converted tokens are sent to the sender.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct ConvertOptions {
+    pub callback: Option<Callback>,
+    pub memo: Option<String>,
+    pub splits: Option<Vec<Split>>,
+    pub vault_deposit: Option<VaultDeposit>,
+    /// Converts as much as the reserve covers and refunds the rest, instead of failing
+    #[serde(default)]
+    pub allow_partial: bool,
+}
+
+/// Deposits the converted tokens into a whitelisted `vault` on behalf of `recipient`, the sender
+/// when unset.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VaultDeposit {
+    pub vault: String,
+    pub recipient: Option<String>,
+}
+
+/// Share of a conversion's output paid to `recipient`, in basis points of the whole.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Split {
+    pub recipient: String,
+    pub bps: u16,
+}
+
+/// Delivered to `contract` as a `ReceiveConversionMsg` once a conversion succeeds, so that other
+/// contracts can compose "convert then do X" in a single transaction.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Callback {
+    pub contract: String,
+    pub msg: Binary,
+    pub funds_policy: FundsPolicy,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FundsPolicy {
+    /// The converted tokens are attached to the callback instead of being sent to the sender
+    Attach,
+    /// The converted tokens are sent to the sender and the callback carries no funds
+    SendToSender,
+}
+
+/// Query the compliance registry must answer with an `IsAllowedResponse`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplianceQueryMsg {
+    IsAllowed { address: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IsAllowedResponse {
+    pub allowed: bool,
+}
+
+/// Sent to every registered hook contract after a conversion completes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConversionHookMsg {
+    pub id: u64,
+    pub sender: String,
+    pub src_amount: Uint128,
+    pub dest_amount: Uint128,
+}
+
+impl ConversionHookMsg {
+    /// serializes the message
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = ConversionExecuteMsg::ConversionHook(self);
+        to_binary(&msg)
+    }
+
+    /// creates a cosmos_msg sending this struct to the named contract
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+/// Sent to a conversion's callback contract, wrapping the caller-supplied `msg` so the converter
+/// never executes arbitrary messages in its own name.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiveConversionMsg {
+    pub sender: String,
+    pub amount_out: Uint128,
+    pub id: u64,
+    pub msg: Binary,
+}
+
+impl ReceiveConversionMsg {
+    /// serializes the message
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = ReceiverExecuteMsg::ReceiveConversion(self);
+        to_binary(&msg)
+    }
+
+    /// creates a cosmos_msg sending this struct and `funds` to the named contract
+    pub fn into_cosmos_msg<T: Into<String>>(
+        self,
+        contract_addr: T,
+        funds: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds,
+        };
+        Ok(execute.into())
+    }
+}
+
+/// `Deposit` message executed on a whitelisted vault. It is always built by the converter, never
+/// taken from the caller.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VaultDepositMsg {
+    pub recipient: String,
+}
+
+impl VaultDepositMsg {
+    /// serializes the message
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = VaultExecuteMsg::Deposit(self);
+        to_binary(&msg)
+    }
+
+    /// creates a cosmos_msg depositing `funds` into the named vault
+    pub fn into_cosmos_msg<T: Into<String>>(
+        self,
+        contract_addr: T,
+        funds: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds,
+        };
+        Ok(execute.into())
+    }
+}
+
+/// What a conversion receipt NFT records about its conversion.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptMetadata {
+    pub id: u64,
+    pub src_denom: String,
+    pub src_amount: Uint128,
+    pub dest_denom: String,
+    pub dest_amount: Uint128,
+    pub height: u64,
+    pub memo: Option<String>,
+}
+
+/// cw721-base `Mint` message for a conversion receipt, owned by the converting address.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptMintMsg {
+    pub token_id: String,
+    pub owner: String,
+    pub token_uri: Option<String>,
+    pub extension: ReceiptMetadata,
+}
+
+impl ReceiptMintMsg {
+    /// serializes the message
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = Cw721ExecuteMsg::Mint(self);
+        to_binary(&msg)
+    }
+
+    /// creates a cosmos_msg minting this receipt on the named contract
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum Cw721ExecuteMsg {
+    Mint(ReceiptMintMsg),
+}
+
+// This is just a helper to properly serialize the above message
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ConversionExecuteMsg {
+    ConversionHook(ConversionHookMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ReceiverExecuteMsg {
+    ReceiveConversion(ReceiveConversionMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum VaultExecuteMsg {
+    Deposit(VaultDepositMsg),
+}
@@ -0,0 +1,24 @@
+//! Shared paging rules for every list query, so none of them can be made to read an unbounded
+//! amount of storage.
+
+/// Page size used when a query does not ask for one.
+pub const DEFAULT_LIMIT: u32 = 10;
+/// Largest page a query ever returns, whatever the caller asks for.
+pub const MAX_LIMIT: u32 = 30;
+
+/// Turns a caller supplied `limit` into a page size between 1 and `MAX_LIMIT`.
+pub fn clamp_limit(limit: Option<u32>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_is_clamped() {
+        assert_eq!(DEFAULT_LIMIT as usize, clamp_limit(None));
+        assert_eq!(1, clamp_limit(Some(0)));
+        assert_eq!(MAX_LIMIT as usize, clamp_limit(Some(1000)));
+    }
+}
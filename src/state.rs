@@ -1,17 +1,266 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Addr;
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
+use cw20::Expiration;
+use cw_storage_plus::{Item, Map};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
     pub count: i32,
     pub owner: Addr,
+    /// Seconds a proposed new owner has to wait before accepting ownership
+    pub ownership_transfer_delay: u64,
+}
+
+pub const STATE: Item<State> = Item::new("state");
+
+/// The token pair, kept apart from `State` so conversions only load what they need.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PairConfig {
     pub dest_ic20_denom: String,
     pub dest_ic20_decimals: u8,
     pub src_ic20_denom: String,
     pub src_ic20_decimals: u8,
+    /// One whole destination token in base units, i.e. 10^dest_ic20_decimals
+    pub dest_whole_token: Uint128,
+    /// One whole source token in base units, i.e. 10^src_ic20_decimals
+    pub src_whole_token: Uint128,
 }
 
-pub const STATE: Item<State> = Item::new("state");
+pub const PAIR_CONFIG: Item<PairConfig> = Item::new("pair_config");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingOwnership {
+    pub new_owner: Addr,
+    pub accept_after: Timestamp,
+}
+
+/// Ownership transfer proposed by the owner, waiting out the transfer delay.
+pub const PENDING_OWNERSHIP: Item<PendingOwnership> = Item::new("pending_ownership");
+
+/// Contracts notified with a `ConversionHookMsg` after every conversion.
+pub const HOOKS: Map<&Addr, ()> = Map::new("hooks");
+
+/// Vaults converted tokens may be deposited into.
+pub const VAULTS: Map<&Addr, ()> = Map::new("vaults");
+
+/// Further source denoms accepted as the same asset, e.g. arriving over another IBC channel.
+/// They convert at the same rate as `PairConfig::src_ic20_denom`.
+pub const SRC_PATHS: Item<Vec<String>> = Item::new("src_paths");
+
+/// Source tokens converted so far, per denom.
+pub const PATH_VOLUME: Map<&str, Uint128> = Map::new("path_volume");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptConfig {
+    /// cw721 contract this contract may mint on
+    pub nft_contract: Addr,
+    /// Smallest destination amount that earns a receipt
+    pub threshold: Uint128,
+}
+
+/// Receipt NFTs minted for large conversions, disabled when unset.
+pub const RECEIPTS: Item<ReceiptConfig> = Item::new("receipts");
+
+/// External registry asked whether an address may convert. Conversions are open to everyone
+/// when unset.
+pub const COMPLIANCE_REGISTRY: Item<Addr> = Item::new("compliance_registry");
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Subsidy {
+    /// Bonus paid on top of every conversion output, in basis points
+    pub bonus_bps: u16,
+    /// Destination tokens set aside by the owner to pay the bonus
+    pub pool: Uint128,
+}
+
+pub const SUBSIDY: Item<Subsidy> = Item::new("subsidy");
+
+/// Display information for explorers and generic frontends.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Metadata {
+    pub name: String,
+    pub description: String,
+    pub logo_uri: Option<String>,
+    pub docs_uri: Option<String>,
+}
+
+pub const METADATA: Item<Metadata> = Item::new("metadata");
+
+/// Tokens moved in and out of the contract by its own messages, booked per denom so they
+/// can be reconciled against the live balances.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct LedgerEntry {
+    pub inflows: Uint128,
+    pub outflows: Uint128,
+}
+
+pub const LEDGER: Map<&str, LedgerEntry> = Map::new("ledger");
+
+/// Destination reserve levels, in ascending order, that emit a `reserve_low` event when a
+/// conversion takes the reserve below them.
+pub const RESERVE_THRESHOLDS: Item<Vec<Uint128>> = Item::new("reserve_thresholds");
+
+/// Reward token paid out per unit of converted volume, until `end`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Emission {
+    pub denom: String,
+    /// Reward tokens earned per destination token converted to
+    pub per_unit: Decimal,
+    pub end: Timestamp,
+}
+
+pub const EMISSION: Item<Emission> = Item::new("emission");
+
+/// Reward tokens set aside by the owner per denom. Claims are paid from it and never beyond it.
+pub const REWARD_POOL: Map<&str, Uint128> = Map::new("reward_pool");
+
+/// Unclaimed emission rewards per address and reward denom.
+pub const REWARDS: Map<(&Addr, &str), Uint128> = Map::new("rewards");
+
+/// Promotional bonus on conversions, for a limited time and volume.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Offer {
+    /// Bonus paid on top of the conversion output, in basis points
+    pub rate_bonus_bps: u16,
+    /// Destination amount a single address may convert under the offer
+    pub per_user_cap: Uint128,
+    /// Destination amount that may be converted under the offer overall
+    pub total_cap: Uint128,
+    pub expiration: Expiration,
+    /// Destination amount converted under the offer so far
+    pub used: Uint128,
+}
+
+pub const OFFERS: Map<u64, Offer> = Map::new("offers");
+
+/// Number of offers ever posted, also used as the id of the next one.
+pub const OFFER_COUNT: Item<u64> = Item::new("offer_count");
+
+/// Destination amount each address converted under each offer.
+pub const OFFER_USAGE: Map<(u64, &Addr), Uint128> = Map::new("offer_usage");
+
+/// Destination tokens each address deposited as conversion liquidity, keyed by the generation
+/// of the deposit book they were made in. Only the current generation counts.
+pub const DEPOSITS: Map<(u64, &Addr), Uint128> = Map::new("deposits");
+
+/// Current generation of the deposit book. Sweeping the reserve starts a new one, which drops
+/// every earlier deposit without iterating over them.
+pub const DEPOSIT_GENERATION: Item<u64> = Item::new("deposit_generation");
+
+/// Sum of the deposits of the current generation.
+pub const TOTAL_DEPOSITS: Item<Uint128> = Item::new("total_deposits");
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct RoundTrip {
+    /// Block time of the address's last conversion
+    pub last_conversion: Timestamp,
+    /// Destination tokens the address may still convert back, bonuses excluded
+    pub returnable: Uint128,
+}
+
+/// What each address converted, so converting back cannot return bonuses or follow a
+/// conversion right away.
+pub const ROUND_TRIPS: Map<&Addr, RoundTrip> = Map::new("round_trips");
+
+/// Non-transferable loyalty points per address, earned by converting.
+pub const POINTS: Map<&Addr, Uint128> = Map::new("points");
+
+/// Counters bumped on every operation, kept in one item so each operation writes them once.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Counters {
+    /// Number of conversions performed so far, also used as the id of the next one
+    pub conversions: u64,
+    /// Total number of operations ever recorded in `ACTIVITY`
+    pub activity: u64,
+    /// How many of those were imported from a previous deployment rather than performed here
+    #[serde(default)]
+    pub imported: u64,
+    /// Running totals of every conversion recorded in `ACTIVITY`
+    #[serde(default)]
+    pub converted: ConversionTotals,
+}
+
+pub const COUNTERS: Item<Counters> = Item::new("counters");
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct ConversionTotals {
+    pub count: u64,
+    pub amount_in: Uint128,
+    pub amount_out: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RateCheckpoint {
+    /// Block time of the conversion
+    pub time: Timestamp,
+    /// Totals of the conversions recorded before it
+    pub before: ConversionTotals,
+}
+
+/// Checkpoint of each of the most recent conversions, keyed by its position among all
+/// conversions modulo `ACTIVITY_CAPACITY`. The totals over any run of them are the difference
+/// between the running totals and the checkpoint of the first.
+pub const RATE_CHECKPOINTS: Map<u64, RateCheckpoint> = Map::new("rate_checkpoints");
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Everything is available
+    Active,
+    /// Conversions are on hold until the owner switches back to `Active`
+    Paused,
+    /// Winding down: new conversions are rejected, everything else keeps working
+    Deprecated,
+    /// Shut down for good by `ExecuteMsg::Close`, every execute fails
+    Closed,
+}
+
+/// Operating mode set by the owner, `Mode::Active` when unset.
+pub const MODE: Item<Mode> = Item::new("mode");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// May add and remove conversion hooks
+    HookManager,
+    /// May pause the contract, and nothing else
+    Guardian,
+    /// May manage vaults, receipts, reserve thresholds and metadata
+    Operator,
+    /// May fund and configure the subsidy, offers and emission rewards
+    IncentiveManager,
+}
+
+/// Privileges granted by the owner to other addresses. The owner implicitly holds every role.
+pub const ROLES: Map<&Addr, Vec<Role>> = Map::new("roles");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Conversion,
+    /// Destination tokens converted back into the source token
+    ConversionBack,
+    Deposit,
+    Withdrawal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ActivityEntry {
+    pub kind: ActivityKind,
+    pub address: Addr,
+    pub amount_in: Uint128,
+    pub amount_out: Uint128,
+    pub height: u64,
+    /// Block time of the operation, unset on entries recorded before it was tracked
+    #[serde(default)]
+    pub time: Timestamp,
+    pub memo: Option<String>,
+}
+
+/// How many operations the recent activity ring buffer holds.
+pub const ACTIVITY_CAPACITY: u64 = 100;
+
+/// Ring buffer of the most recent operations, keyed by sequence number modulo `ACTIVITY_CAPACITY`.
+pub const ACTIVITY: Map<u64, ActivityEntry> = Map::new("activity");
@@ -0,0 +1,95 @@
+//! Helpers to run the converter inside a cw-multi-test `App`, so integrators can test their
+//! own contracts against it. Enable with the `testing` feature.
+
+use cosmwasm_std::{Addr, Coin, Empty, Uint128};
+use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
+
+use crate::contract::{execute, instantiate, query, reply};
+use crate::msg::InstantiateMsg;
+
+pub fn contract_converter() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(execute, instantiate, query).with_reply(reply);
+    Box::new(contract)
+}
+
+/// Creates an `App` where every `(address, coins)` pair starts out with the given balance.
+pub fn mock_app(init_funds: &[(&str, Vec<Coin>)]) -> App {
+    AppBuilder::new().build(|router, _, storage| {
+        for (addr, coins) in init_funds {
+            router
+                .bank
+                .init_balance(storage, &Addr::unchecked(*addr), coins.clone())
+                .unwrap();
+        }
+    })
+}
+
+/// Stores and instantiates the converter as `owner`, then sends it `reserve` from the owner's
+/// balance so that conversions can be paid out.
+pub fn instantiate_converter(
+    app: &mut App,
+    owner: &str,
+    msg: &InstantiateMsg,
+    reserve: &[Coin],
+) -> Addr {
+    let code_id = app.store_code(contract_converter());
+    let converter = app
+        .instantiate_contract(
+            code_id,
+            Addr::unchecked(owner),
+            msg,
+            &[],
+            "converter",
+            Some(owner.to_string()),
+        )
+        .unwrap();
+    if !reserve.is_empty() {
+        app.send_tokens(Addr::unchecked(owner), converter.clone(), reserve)
+            .unwrap();
+    }
+    converter
+}
+
+pub fn query_balance(app: &App, addr: &Addr, denom: &str) -> Uint128 {
+    app.wrap().query_balance(addr, denom).unwrap().amount
+}
+
+pub fn assert_balance(app: &App, addr: &Addr, denom: &str, expected: u128) {
+    assert_eq!(
+        Uint128::new(expected),
+        query_balance(app, addr, denom),
+        "balance of {} in {}",
+        addr,
+        denom
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::{CountResponse, QueryMsg};
+    use cosmwasm_std::coins;
+
+    #[test]
+    fn instantiate_with_reserve() {
+        let mut app = mock_app(&[("owner", coins(1_000, "cosmostoken"))]);
+        let msg = InstantiateMsg {
+            count: 17,
+            src_ic20_decimals: 18,
+            src_ic20_denom: "erc20token".to_string(),
+            dest_ic20_decimals: 6,
+            dest_ic20_denom: "cosmostoken".to_string(),
+            ownership_transfer_delay: 3600,
+            start_paused: false,
+        };
+        let converter = instantiate_converter(&mut app, "owner", &msg, &coins(400, "cosmostoken"));
+
+        assert_balance(&app, &converter, "cosmostoken", 400);
+        assert_balance(&app, &Addr::unchecked("owner"), "cosmostoken", 600);
+        let res: CountResponse = app
+            .wrap()
+            .query_wasm_smart(&converter, &QueryMsg::GetCount {})
+            .unwrap();
+        assert_eq!(17, res.count);
+    }
+}